@@ -10,16 +10,848 @@
 * You should have received a copy of the GNU General Public License along with ncsu_exam_calendar. If not, see <https://www.gnu.org/licenses/>.
 */
 
-use std::fs::File;
+mod serve;
+mod watch;
 
-use ncsu_cal_lib::calendar::get_calendars;
+use std::{
+    fmt::Display,
+    fs::{self, File},
+    io::{self, BufReader, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{Arc, PoisonError, RwLock},
+    time::Duration,
+};
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() {
-    let cals = get_calendars("https://studentservices.ncsu.edu/calendars/exam-calendar/")
+use anyhow::{anyhow, Context};
+use chrono::{NaiveDate, NaiveTime, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use ncsu_cal_lib::{
+    batch::assign_exams,
+    cache::PageCache,
+    calendar::{Calendar, CalendarMap, Class, Exam, MatchKind, ParseReport},
+    fetch::{ReqwestFetcher, DEFAULT_USER_AGENT},
+    get_page_document_with,
+    ics::{calendar_map_to_ics, calendar_to_ics, classes_from_ics},
+    rules::{Rule, RuleReport},
+    schedule::{add_class, class_label, Schedule},
+    semester::Semester,
+};
+use serve::Snapshot;
+use watch::{Actions, Snapshots};
+
+const DEFAULT_URL: &str = "https://studentservices.ncsu.edu/calendars/exam-calendar/";
+const DEFAULT_JSON: &str = "exams.json";
+
+/// Process exit status. 2 is left to clap for usage errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Success = 0,
+    /// `diff` found moved exams, or `watch --once` found any change.
+    Changed = 1,
+    Network = 3,
+    /// The page, JSON or a class could not be read.
+    Parse = 4,
+    Io = 5,
+    /// The requested semester or class is not in the calendar.
+    NotFound = 6,
+    /// `validate` found problems.
+    Invalid = 7,
+}
+
+struct Failure {
+    status: Status,
+    error: anyhow::Error,
+}
+
+trait OrStatus<T> {
+    fn or_status(self, status: Status) -> Result<T, Failure>;
+}
+
+impl<T, E: Into<anyhow::Error>> OrStatus<T> for Result<T, E> {
+    fn or_status(self, status: Status) -> Result<T, Failure> {
+        self.map_err(|error| Failure {
+            status,
+            error: error.into(),
+        })
+    }
+}
+
+type CliResult<T = Status> = Result<T, Failure>;
+
+#[derive(Parser)]
+#[command(name = "ncsu_exam_cli", version, about = "NCSU exam calendar tools")]
+struct Cli {
+    /// Only print errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Print progress as well as warnings
+    #[arg(short, long, global = true)]
+    verbose: bool,
+    #[command(flatten)]
+    network: Network,
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// How to download the exam page.
+#[derive(Args)]
+struct Network {
+    /// Seconds to wait for each request, 0 for no limit
+    #[arg(long, global = true, default_value_t = 30)]
+    timeout: u64,
+    /// Extra attempts after a network or server error
+    #[arg(long, global = true, default_value_t = 2)]
+    retries: u32,
+    /// Milliseconds before the first retry, doubled for each one after
+    #[arg(long, global = true, default_value_t = 500)]
+    backoff: u64,
+    #[arg(long, global = true, default_value = DEFAULT_USER_AGENT)]
+    user_agent: String,
+    /// Send requests through this proxy
+    #[arg(long, global = true)]
+    proxy: Option<String>,
+    /// Keep the last downloaded page here, and skip parsing it again when unchanged
+    #[arg(long, global = true)]
+    cache: Option<PathBuf>,
+}
+
+impl Network {
+    fn fetcher(&self) -> CliResult<ReqwestFetcher> {
+        ReqwestFetcher::builder()
+            .timeout((self.timeout > 0).then(|| Duration::from_secs(self.timeout)))
+            .retries(self.retries)
+            .backoff(Duration::from_millis(self.backoff))
+            .user_agent(&self.user_agent)
+            .proxy(self.proxy.as_ref())
+            .build()
+            .or_status(Status::Network)
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Download the exam calendar page and convert it
+    Fetch {
+        #[arg(long, default_value = DEFAULT_URL)]
+        url: String,
+        #[command(flatten)]
+        output: Output,
+    },
+    /// Convert a saved copy of the exam calendar page
+    Parse {
+        page: PathBuf,
+        #[command(flatten)]
+        output: Output,
+    },
+    /// Find the exam for one class
+    Lookup {
+        #[command(flatten)]
+        source: Source,
+        /// e.g. "Fall 2023"
+        #[arg(short, long)]
+        semester: Semester,
+        /// e.g. "MWF 10:15am", "TuTh 1:30 pm", "7:30-8:55" or "CSC 316"
+        class: String,
+    },
+    /// Write every semester, or just one, as JSON or iCalendar
+    Export {
+        #[command(flatten)]
+        source: Source,
+        /// Only this semester, e.g. "Fall 2023"
+        #[arg(short, long)]
+        semester: Option<Semester>,
+        #[command(flatten)]
+        output: Output,
+    },
+    /// Check that a calendar loads without skipped or duplicate entries
+    Validate {
+        #[command(flatten)]
+        source: Source,
+    },
+    /// List semesters and classes added, removed or moved between two calendar JSON files.
+    /// Exits with 1 when a class's exams moved
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        /// Print the changes as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Add exams to a CSV of sections (course, days, start columns)
+    Batch {
+        #[command(flatten)]
+        source: Source,
+        /// e.g. "Fall 2023"
+        #[arg(short, long)]
+        semester: Semester,
+        sections: PathBuf,
+        /// Write here instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Serve the calendars over HTTP for other web pages
+    Serve {
+        #[command(flatten)]
+        source: Source,
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+        /// Reload the source every this many minutes
+        #[arg(long)]
+        refresh: Option<u64>,
+        /// Value of the Access-Control-Allow-Origin header
+        #[arg(long, default_value = "*")]
+        allow_origin: String,
+    },
+    /// Check the exam page on an interval, saving and reporting each change
+    Watch {
+        #[arg(long, default_value = DEFAULT_URL)]
+        url: String,
+        /// Minutes between checks
+        #[arg(long, default_value_t = 60)]
+        interval: u64,
+        /// Where each changed calendar is saved, named by time
+        #[arg(long, default_value = "snapshots")]
+        snapshots: PathBuf,
+        /// Shell command to run on a change, given the change as JSON on stdin
+        #[arg(long)]
+        exec: Option<String>,
+        /// URL to POST the change to as JSON
+        #[arg(long)]
+        webhook: Option<String>,
+        /// File to append each change to
+        #[arg(long)]
+        changelog: Option<PathBuf>,
+        /// Check once and exit, with status 1 if anything changed
+        #[arg(long)]
+        once: bool,
+    },
+    /// List every class in each exam slot
+    Slots {
+        #[command(flatten)]
+        source: Source,
+        /// e.g. "Fall 2023"
+        #[arg(short, long)]
+        semester: Semester,
+        /// Only this day (YYYY-MM-DD)
+        #[arg(long)]
+        date: Option<NaiveDate>,
+        /// Only slots starting at this time (HH:MM)
+        #[arg(long)]
+        start: Option<NaiveTime>,
+    },
+    /// List the exams for several classes, with conflicts and overloads
+    Schedule {
+        #[command(flatten)]
+        source: Source,
+        /// e.g. "Fall 2023"
+        #[arg(short, long)]
+        semester: Semester,
+        #[command(flatten)]
+        rules: RuleArgs,
+        /// Also every weekly class in a class schedule exported as iCalendar
+        #[arg(long)]
+        ics: Option<PathBuf>,
+        /// Classes like "MWF 10:15am" or "PY 211"
+        #[arg(required_unless_present = "ics")]
+        classes: Vec<String>,
+    },
+}
+
+#[derive(Args)]
+struct Source {
+    /// Calendar JSON, or a saved exam page ending in .html
+    #[arg(short, long, default_value = DEFAULT_JSON)]
+    input: PathBuf,
+    /// Download the exam page instead of reading --input
+    #[arg(long, conflicts_with = "input")]
+    url: Option<String>,
+}
+
+#[derive(Args)]
+struct Output {
+    /// Write here instead of stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    #[arg(short, long, value_enum, default_value_t = Format::Pretty)]
+    format: Format,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Json,
+    Pretty,
+    Ics,
+}
+
+/// Overrides for [`Rule::defaults`].
+#[derive(Args)]
+struct RuleArgs {
+    /// Exams in one day that count as an overload
+    #[arg(long)]
+    per_day: Option<usize>,
+    /// Exams within --window-hours that count as an overload
+    #[arg(long)]
+    per_window: Option<usize>,
+    /// Length of the --per-window window
+    #[arg(long)]
+    window_hours: Option<i64>,
+    /// Exams in back to back slots that count as an overload
+    #[arg(long)]
+    consecutive: Option<usize>,
+}
+
+impl RuleArgs {
+    fn rules(&self) -> Vec<Rule> {
+        Rule::defaults()
+            .into_iter()
+            .map(|rule| match rule {
+                Rule::ExamsPerDay { count } => Rule::ExamsPerDay {
+                    count: self.per_day.unwrap_or(count),
+                },
+                Rule::ExamsInWindow { count, hours } => Rule::ExamsInWindow {
+                    count: self.per_window.unwrap_or(count),
+                    hours: self.window_hours.unwrap_or(hours),
+                },
+                Rule::ConsecutiveSlots { count } => Rule::ConsecutiveSlots {
+                    count: self.consecutive.unwrap_or(count),
+                },
+            })
+            .collect()
+    }
+}
+
+struct Ui {
+    quiet: bool,
+    verbose: bool,
+    fetcher: ReqwestFetcher,
+    cache: Option<PageCache>,
+}
+
+impl Ui {
+    fn info(&self, message: impl Display) {
+        if self.verbose {
+            eprintln!("{message}");
+        }
+    }
+
+    fn warn(&self, message: impl Display) {
+        if !self.quiet {
+            eprintln!("{message}");
+        }
+    }
+
+    /// Warns about everything skipped or listed twice, returning how many problems there were.
+    fn report(&self, cals: &CalendarMap, report: &ParseReport) -> usize {
+        if !report.is_empty() {
+            self.warn(format!(
+                "Skipped while parsing:\n{}",
+                report.to_string().trim_end()
+            ));
+        }
+        let mut problems = report.skipped.len();
+        for (semester, calendar) in cals.iter() {
+            for (class, exams) in calendar.duplicates() {
+                self.warn(format!(
+                    "{semester}: {class} is listed with {} exams",
+                    exams.len()
+                ));
+                problems += 1;
+            }
+        }
+        problems
+    }
+}
+
+fn open(path: &Path) -> CliResult<File> {
+    File::open(path)
+        .with_context(|| format!("Could not open {}", path.display()))
+        .or_status(Status::Io)
+}
+
+fn is_html(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm"))
+}
+
+async fn fetch(url: &str, ui: &Ui) -> CliResult<(CalendarMap, ParseReport)> {
+    ui.info(format!("Fetching {url}"));
+    if let Some(cache) = &ui.cache {
+        let cached = cache
+            .get_calendars(&ui.fetcher, url)
+            .await
+            .with_context(|| format!("Could not fetch {url}"))
+            .or_status(Status::Network)?;
+        if cached.from_cache {
+            ui.info("Unchanged, using the cached page");
+        }
+        ui.info(&cached.meta);
+        return Ok((cached.calendars, cached.report));
+    }
+
+    let page = get_page_document_with(&ui.fetcher, url)
         .await
-        .unwrap();
-    println!("{}", serde_json::to_string_pretty(&cals).unwrap());
-    let create = File::create("exams.json").unwrap();
-    serde_json::to_writer(create, &cals).unwrap();
+        .with_context(|| format!("Could not fetch {url}"))
+        .or_status(Status::Network)?;
+    CalendarMap::parse(&page).or_status(Status::Parse)
+}
+
+fn parse_page(path: &Path) -> CliResult<(CalendarMap, ParseReport)> {
+    CalendarMap::from_reader(open(path)?).or_status(Status::Parse)
+}
+
+fn read_json(path: &Path) -> CliResult<CalendarMap> {
+    serde_json::from_reader(BufReader::new(open(path)?))
+        .with_context(|| format!("{} is not a calendar JSON file", path.display()))
+        .or_status(Status::Parse)
+}
+
+async fn load(source: &Source, ui: &Ui) -> CliResult<(CalendarMap, ParseReport)> {
+    let (cals, report) = if let Some(url) = &source.url {
+        fetch(url, ui).await?
+    } else if is_html(&source.input) {
+        parse_page(&source.input)?
+    } else {
+        (read_json(&source.input)?, ParseReport::default())
+    };
+    ui.info(format!("Loaded {} semesters", cals.len()));
+    Ok((cals, report))
+}
+
+fn semester<'a>(cals: &'a CalendarMap, semester: &Semester) -> CliResult<&'a Calendar> {
+    cals.get(semester)
+        .ok_or_else(|| {
+            anyhow!(
+                "{semester} is not in the calendar (have: {})",
+                cals.keys()
+                    .map(Semester::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+        .or_status(Status::NotFound)
+}
+
+fn parse_class(class: &str) -> CliResult<Class> {
+    class
+        .parse::<Class>()
+        .with_context(|| format!("Could not read class \"{class}\""))
+        .or_status(Status::Parse)
+}
+
+fn write_output(
+    cals: &CalendarMap,
+    selected: Option<&Semester>,
+    output: &Output,
+    ui: &Ui,
+) -> CliResult {
+    let text = match (output.format, selected) {
+        (Format::Ics, Some(selected)) => calendar_to_ics(selected, semester(cals, selected)?),
+        (Format::Ics, None) => calendar_map_to_ics(cals),
+        (format, selected) => {
+            let single: CalendarMap;
+            let cals = if let Some(selected) = selected {
+                single = [(*selected, semester(cals, selected)?.clone())]
+                    .into_iter()
+                    .collect();
+                &single
+            } else {
+                cals
+            };
+            if format == Format::Pretty {
+                serde_json::to_string_pretty(cals)
+            } else {
+                serde_json::to_string(cals)
+            }
+            .or_status(Status::Parse)?
+        }
+    };
+
+    if let Some(path) = &output.output {
+        fs::write(path, text)
+            .with_context(|| format!("Could not write {}", path.display()))
+            .or_status(Status::Io)?;
+        ui.info(format!("Wrote {}", path.display()));
+    } else {
+        writeln!(io::stdout().lock(), "{}", text.trim_end()).or_status(Status::Io)?;
+    }
+    Ok(Status::Success)
+}
+
+fn lookup(cals: &CalendarMap, selected: &Semester, class: &str, ui: &Ui) -> CliResult {
+    let calendar = semester(cals, selected)?;
+    let class = parse_class(class)?;
+    let found = calendar
+        .resolve(&class)
+        .ok_or_else(|| anyhow!("No exam found for {class} in {selected}"))
+        .or_status(Status::NotFound)?;
+
+    if found.exams.len() > 1 {
+        println!("Exams (this class is listed more than once):");
+    } else {
+        println!("Exam:");
+    }
+    if found.kind != MatchKind::Exact {
+        println!("Matched by {} ({})", found.kind, found.class);
+    }
+    if let Some(ambiguity) = found.ambiguity() {
+        ui.warn(ambiguity);
+    }
+    for exam in found.exams {
+        println!("{exam}");
+    }
+    Ok(Status::Success)
+}
+
+fn validate(cals: &CalendarMap, report: &ParseReport, ui: &Ui) -> Status {
+    let mut problems = ui.report(cals, report);
+    for (semester, calendar) in cals.iter() {
+        if calendar.is_empty() {
+            ui.warn(format!("{semester} has no exams"));
+            problems += 1;
+        }
+    }
+    if problems == 0 {
+        ui.info(format!("{} semesters, no problems", cals.len()));
+        Status::Success
+    } else {
+        ui.warn(format!("{problems} problems"));
+        Status::Invalid
+    }
+}
+
+fn batch(calendar: &Calendar, sections: &Path, output: Option<&Path>, ui: &Ui) -> CliResult {
+    let input = open(sections)?;
+    let summary = if let Some(path) = output {
+        let file = File::create(path)
+            .with_context(|| format!("Could not create {}", path.display()))
+            .or_status(Status::Io)?;
+        assign_exams(calendar, input, file)
+    } else {
+        assign_exams(calendar, input, io::stdout().lock())
+    }
+    .with_context(|| format!("Could not process {}", sections.display()))
+    .or_status(Status::Parse)?;
+
+    if summary.unresolved > 0 {
+        ui.warn(format!(
+            "{} of {} sections have no exam (see the error column)",
+            summary.unresolved, summary.rows
+        ));
+    } else {
+        ui.info(format!("Assigned exams to {} sections", summary.rows));
+    }
+    if summary.ambiguous > 0 {
+        ui.warn(format!(
+            "{} sections matched several listings (see the note column)",
+            summary.ambiguous
+        ));
+    }
+    Ok(Status::Success)
+}
+
+async fn serve(
+    source: &Source,
+    addr: &str,
+    refresh: Option<u64>,
+    allow_origin: &str,
+    ui: &Ui,
+) -> CliResult {
+    let (cals, report) = load(source, ui).await?;
+    ui.report(&cals, &report);
+    let snapshot = Arc::new(RwLock::new(Snapshot::new(cals).or_status(Status::Parse)?));
+    let server = serve::spawn(addr, allow_origin, snapshot.clone()).or_status(Status::Io)?;
+    ui.warn(format!("Serving on http://{addr}"));
+
+    let Some(minutes) = refresh else {
+        let _ = server.join();
+        return Ok(Status::Success);
+    };
+    loop {
+        tokio::time::sleep(Duration::from_secs(minutes * 60)).await;
+        match load(source, ui).await {
+            Ok((cals, report)) => {
+                ui.report(&cals, &report);
+                match Snapshot::new(cals) {
+                    Ok(new) => {
+                        *snapshot.write().unwrap_or_else(PoisonError::into_inner) = new;
+                        ui.info("Reloaded the calendars");
+                    }
+                    Err(e) => ui.warn(format!("Reload failed, serving the old calendars: {e:#}")),
+                }
+            }
+            Err(Failure { error, .. }) => ui.warn(format!(
+                "Reload failed, serving the old calendars: {error:#}"
+            )),
+        }
+    }
+}
+
+async fn watch(
+    url: &str,
+    interval: u64,
+    snapshots: &Snapshots,
+    actions: &Actions,
+    once: bool,
+    ui: &Ui,
+) -> CliResult {
+    let mut last = snapshots
+        .latest()
+        .or_status(Status::Io)?
+        .map(|(path, cals)| {
+            ui.info(format!("Comparing against {}", path.display()));
+            cals
+        });
+
+    loop {
+        let changed = match fetch(url, ui).await {
+            Ok((cals, _)) => watch_check(url, &mut last, cals, snapshots, actions, ui).await?,
+            Err(failure) if once => return Err(failure),
+            Err(Failure { error, .. }) => {
+                ui.warn(format!("Check failed, trying again later: {error:#}"));
+                false
+            }
+        };
+        if once {
+            return Ok(if changed {
+                Status::Changed
+            } else {
+                Status::Success
+            });
+        }
+        tokio::time::sleep(Duration::from_secs(interval * 60)).await;
+    }
+}
+
+/// Compares `cals` with the last snapshot, acting on any change.
+async fn watch_check(
+    url: &str,
+    last: &mut Option<CalendarMap>,
+    cals: CalendarMap,
+    snapshots: &Snapshots,
+    actions: &Actions,
+    ui: &Ui,
+) -> CliResult<bool> {
+    let now = Utc::now();
+    let Some(previous) = last else {
+        let path = snapshots.save(&cals, now).or_status(Status::Io)?;
+        ui.warn(format!("Saved the first snapshot to {}", path.display()));
+        *last = Some(cals);
+        return Ok(false);
+    };
+
+    let diff = previous.diff(&cals);
+    if diff.is_empty() {
+        ui.info(format!("No changes as of {}", now.to_rfc3339()));
+        return Ok(false);
+    }
+
+    let path = snapshots.save(&cals, now).or_status(Status::Io)?;
+    ui.warn(format!(
+        "Changed, saved to {}:\n{}",
+        path.display(),
+        diff.to_string().trim_end()
+    ));
+    for error in actions.run(url, now, &path, &diff).await {
+        ui.warn(format!("Action failed: {error:#}"));
+    }
+    *last = Some(cals);
+    Ok(true)
+}
+
+fn diff(old: &Path, new: &Path, json: bool) -> CliResult {
+    let diff = read_json(old)?.diff(&read_json(new)?);
+    let mut stdout = io::stdout().lock();
+    let written = if json {
+        serde_json::to_writer_pretty(&mut stdout, &diff)
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(stdout))
+    } else {
+        write!(stdout, "{diff}")
+    };
+    written
+        .context("Could not write to stdout")
+        .or_status(Status::Io)?;
+
+    // Added or removed classes alone don't change anyone's exam
+    Ok(if diff.has_moved() {
+        Status::Changed
+    } else {
+        Status::Success
+    })
+}
+
+fn print_slots(calendar: &Calendar, date: Option<NaiveDate>, start: Option<NaiveTime>) {
+    let matches_filter = |exam: &Exam| {
+        date.is_none_or(|date| exam.date == date)
+            && start.is_none_or(|start| exam.time.start == start)
+    };
+    for (exam, classes) in calendar.by_exam() {
+        if matches_filter(exam) {
+            println!("{exam}");
+            for class in classes {
+                println!("    {class}");
+            }
+        }
+    }
+}
+
+fn print_schedule(calendar: &Calendar, classes: Vec<(Option<String>, Class)>, rules: &[Rule]) {
+    let schedule = Schedule::from_entries(calendar, classes);
+
+    for scheduled in &schedule.exams {
+        println!("{scheduled}");
+    }
+    for (label, class) in &schedule.unresolved {
+        println!("{}: no exam found", class_label(label, class));
+    }
+    for conflict in schedule.conflicts() {
+        println!(
+            "Conflict, {}: {} and {}",
+            conflict.kind,
+            conflict.first.name(),
+            conflict.second.name()
+        );
+    }
+    print!("{}", RuleReport::new(rules, &schedule, calendar));
+}
+
+async fn run(command: Command, ui: &Ui) -> CliResult {
+    match command {
+        Command::Fetch { url, output } => {
+            let (cals, report) = fetch(&url, ui).await?;
+            ui.report(&cals, &report);
+            write_output(&cals, None, &output, ui)
+        }
+        Command::Parse { page, output } => {
+            let (cals, report) = parse_page(&page)?;
+            ui.report(&cals, &report);
+            write_output(&cals, None, &output, ui)
+        }
+        Command::Lookup {
+            source,
+            semester,
+            class,
+        } => {
+            let (cals, report) = load(&source, ui).await?;
+            ui.report(&cals, &report);
+            lookup(&cals, &semester, &class, ui)
+        }
+        Command::Export {
+            source,
+            semester,
+            output,
+        } => {
+            let (cals, report) = load(&source, ui).await?;
+            ui.report(&cals, &report);
+            write_output(&cals, semester.as_ref(), &output, ui)
+        }
+        Command::Validate { source } => {
+            let (cals, report) = load(&source, ui).await?;
+            Ok(validate(&cals, &report, ui))
+        }
+        Command::Diff { old, new, json } => diff(&old, &new, json),
+        Command::Serve {
+            source,
+            addr,
+            refresh,
+            allow_origin,
+        } => serve(&source, &addr, refresh, &allow_origin, ui).await,
+        Command::Watch {
+            url,
+            interval,
+            snapshots,
+            exec,
+            webhook,
+            changelog,
+            once,
+        } => {
+            let actions = Actions {
+                exec,
+                webhook,
+                changelog,
+                client: ui.fetcher.clone(),
+            };
+            watch(
+                &url,
+                interval,
+                &Snapshots::new(snapshots),
+                &actions,
+                once,
+                ui,
+            )
+            .await
+        }
+        Command::Batch {
+            source,
+            semester: selected,
+            sections,
+            output,
+        } => {
+            let (cals, report) = load(&source, ui).await?;
+            ui.report(&cals, &report);
+            batch(
+                semester(&cals, &selected)?,
+                &sections,
+                output.as_deref(),
+                ui,
+            )
+        }
+        Command::Slots {
+            source,
+            semester: selected,
+            date,
+            start,
+        } => {
+            let (cals, report) = load(&source, ui).await?;
+            ui.report(&cals, &report);
+            print_slots(semester(&cals, &selected)?, date, start);
+            Ok(Status::Success)
+        }
+        Command::Schedule {
+            source,
+            semester: selected,
+            rules,
+            ics,
+            classes,
+        } => {
+            let (cals, report) = load(&source, ui).await?;
+            ui.report(&cals, &report);
+            let mut entries = vec![];
+            for class in &classes {
+                add_class(&mut entries, None, parse_class(class)?);
+            }
+            if let Some(path) = ics {
+                let text = fs::read_to_string(&path)
+                    .with_context(|| format!("Could not read {}", path.display()))
+                    .or_status(Status::Io)?;
+                let imported = classes_from_ics(&text).or_status(Status::Parse)?;
+                ui.info(format!("Imported {} classes", imported.len()));
+                for (label, class) in imported {
+                    add_class(&mut entries, Some(label), class);
+                }
+            }
+            print_schedule(semester(&cals, &selected)?, entries, &rules.rules());
+            Ok(Status::Success)
+        }
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let ui = cli.network.fetcher().map(|fetcher| Ui {
+        quiet: cli.quiet,
+        verbose: cli.verbose,
+        fetcher,
+        cache: cli.network.cache.map(PageCache::new),
+    });
+    let status = match async { run(cli.command, &ui?).await }.await {
+        Ok(status) => status,
+        Err(Failure { status, error }) => {
+            eprintln!("Error: {error:#}");
+            status
+        }
+    };
+    ExitCode::from(status as u8)
 }
@@ -0,0 +1,364 @@
+/*
+* Copyright (C) 2023 Bennett Petzold
+*
+* This file is part of ncsu_exam_calendar.
+*
+* ncsu_exam_calendar is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 2 of the License, or (at your option) any later version.
+*
+* ncsu_exam_calendar is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License along with ncsu_exam_calendar. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Read-only HTTP API over a [`CalendarMap`], for pages that cannot fetch the exam page themselves.
+
+use std::{
+    sync::{Arc, PoisonError, RwLock},
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{anyhow, Result};
+use ncsu_cal_lib::{
+    calendar::{CalendarMap, Class, Exam},
+    ics::{calendar_map_to_ics, fnv1a},
+    semester::Semester,
+};
+use serde_json::json;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const JSON: &str = "application/json";
+const ICS: &str = "text/calendar; charset=utf-8";
+
+/// The calendars being served, with the whole-map responses rendered once.
+pub struct Snapshot {
+    cals: CalendarMap,
+    json: String,
+    ics: String,
+}
+
+impl Snapshot {
+    pub fn new(cals: CalendarMap) -> Result<Self> {
+        Ok(Self {
+            json: serde_json::to_string(&cals)?,
+            ics: calendar_map_to_ics(&cals),
+            cals,
+        })
+    }
+}
+
+pub type SharedSnapshot = Arc<RwLock<Snapshot>>;
+
+struct Reply {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Reply {
+    fn ok(content_type: &'static str, body: String) -> Self {
+        Self {
+            status: 200,
+            content_type,
+            body,
+        }
+    }
+
+    fn json(value: serde_json::Value) -> Self {
+        Self::ok(JSON, value.to_string())
+    }
+
+    fn error(status: u16, message: impl ToString) -> Self {
+        Self {
+            status,
+            content_type: JSON,
+            body: json!({ "error": message.to_string() }).to_string(),
+        }
+    }
+}
+
+/// Starts answering requests on `addr` in a background thread.
+pub fn spawn(addr: &str, allow_origin: &str, snapshot: SharedSnapshot) -> Result<JoinHandle<()>> {
+    let cors = [
+        ("Access-Control-Allow-Origin", allow_origin),
+        ("Access-Control-Allow-Methods", "GET, HEAD, OPTIONS"),
+        ("Access-Control-Allow-Headers", "If-None-Match"),
+        ("Access-Control-Expose-Headers", "ETag"),
+    ]
+    .into_iter()
+    .map(|(name, value)| header(name, value))
+    .collect::<Result<Vec<_>>>()?;
+    let server = Server::http(addr).map_err(|e| anyhow!("Could not listen on {addr}: {e}"))?;
+
+    Ok(thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = respond(&request, &snapshot, &cors);
+            // The client hanging up early is not our problem
+            let _ = request.respond(response);
+        }
+    }))
+}
+
+fn header(name: &str, value: &str) -> Result<Header> {
+    Header::from_bytes(name.as_bytes(), value.as_bytes())
+        .map_err(|_| anyhow!("Invalid {name} header value \"{value}\""))
+}
+
+fn respond(
+    request: &Request,
+    snapshot: &SharedSnapshot,
+    cors: &[Header],
+) -> Response<std::io::Cursor<Vec<u8>>> {
+    let with_cors = |mut response: Response<_>| {
+        for header in cors {
+            response.add_header(header.clone());
+        }
+        response
+    };
+
+    match request.method() {
+        Method::Options => return with_cors(Response::from_data(vec![]).with_status_code(204)),
+        Method::Get | Method::Head => (),
+        _ => return with_cors(Response::from_data(vec![]).with_status_code(405)),
+    }
+
+    let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+    let reply = route(
+        path,
+        query,
+        &snapshot.read().unwrap_or_else(PoisonError::into_inner),
+    );
+
+    // Stable across restarts and builds, so clients keep their cached copies
+    let etag = format!("\"{:016x}\"", fnv1a(&reply.body));
+    let not_modified = request.headers().iter().any(|header| {
+        header.field.equiv("If-None-Match")
+            && header
+                .value
+                .as_str()
+                .split(',')
+                .any(|tag| tag.trim() == etag || tag.trim() == "*")
+    });
+
+    let response = if reply.status == 200 && not_modified {
+        Response::from_data(vec![]).with_status_code(304)
+    } else {
+        Response::from_string(reply.body).with_status_code(reply.status)
+    };
+    let mut response = with_cors(response);
+    if let Ok(content_type) = header("Content-Type", reply.content_type) {
+        response.add_header(content_type);
+    }
+    if reply.status == 200 {
+        if let Ok(etag) = header("ETag", &etag) {
+            response.add_header(etag);
+        }
+    }
+    response
+}
+
+fn route(path: &str, query: &str, snapshot: &Snapshot) -> Reply {
+    let path = path.trim_end_matches('/');
+    match path {
+        "/exams.json" => Reply::ok(JSON, snapshot.json.clone()),
+        "/exams.ics" => Reply::ok(ICS, snapshot.ics.clone()),
+        "/semesters" => {
+            let mut semesters: Vec<_> = snapshot.cals.keys().collect();
+            semesters.sort();
+            Reply::json(json!(semesters
+                .into_iter()
+                .map(Semester::to_string)
+                .collect::<Vec<_>>()))
+        }
+        "/lookup" => lookup(query, &snapshot.cals),
+        _ => match path.strip_prefix("/semesters/") {
+            Some(id) => match percent_decode(id).parse::<Semester>() {
+                Ok(semester) => match snapshot.cals.get(&semester) {
+                    Some(calendar) => match serde_json::to_string(calendar) {
+                        Ok(body) => Reply::ok(JSON, body),
+                        Err(e) => Reply::error(500, e),
+                    },
+                    None => Reply::error(404, format!("No exams for {semester}")),
+                },
+                Err(e) => Reply::error(400, e),
+            },
+            None => Reply::error(404, format!("No such endpoint: {path}")),
+        },
+    }
+}
+
+/// `?days=MW&time=11:45` or `?class=PY 211`, in `&semester=` (default: the latest semester).
+fn lookup(query: &str, cals: &CalendarMap) -> Reply {
+    let param = |name: &str| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| percent_decode(value))
+    };
+
+    let semester = match param("semester").map(|semester| semester.parse::<Semester>()) {
+        Some(Ok(semester)) => semester,
+        Some(Err(e)) => return Reply::error(400, e),
+        None => match cals.keys().max() {
+            Some(semester) => *semester,
+            None => return Reply::error(404, "No semesters loaded"),
+        },
+    };
+    let Some(calendar) = cals.get(&semester) else {
+        return Reply::error(404, format!("No exams for {semester}"));
+    };
+
+    let text = match (param("class"), param("days"), param("time")) {
+        (Some(class), _, _) => class,
+        (None, Some(days), Some(time)) => format!("{days} {time}"),
+        _ => return Reply::error(400, "Give either class, or both days and time"),
+    };
+    let class = match text.parse::<Class>() {
+        Ok(class) => class,
+        Err(e) => return Reply::error(400, e),
+    };
+
+    let exams = |exams: &[Exam]| {
+        exams
+            .iter()
+            .map(|exam| {
+                json!({
+                    "date": exam.date,
+                    "start": exam.start().to_rfc3339(),
+                    "end": exam.end().to_rfc3339(),
+                })
+            })
+            .collect::<Vec<_>>()
+    };
+    match calendar.resolve(&class) {
+        Some(found) => Reply::json(json!({
+            "semester": semester.to_string(),
+            "class": class.to_string(),
+            "listed": found.class.to_string(),
+            "match_kind": found.kind,
+            "exams": exams(found.exams),
+            // Listings that match just as well, the caller should ask which applies
+            "also": found.others.iter().map(|(listed, others)| json!({
+                "listed": listed.to_string(),
+                "exams": exams(others),
+            })).collect::<Vec<_>>(),
+        })),
+        None => Reply::error(404, format!("No exam found for {class} in {semester}")),
+    }
+}
+
+/// Decodes `%XX` escapes and `+` for spaces.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'%' => match value
+                .get(idx + 1..idx + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => {
+                    out.push(byte);
+                    idx += 3;
+                    continue;
+                }
+                None => out.push(b'%'),
+            },
+            b'+' => out.push(b' '),
+            byte => out.push(byte),
+        }
+        idx += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    const PAGE: &str = r#"<h2>Fall 2023 Exam Calendar</h2>
+<table><thead><tr><th>Exam Dates/Times</th><th>8:30 a.m. – 11:00 a.m.</th><th>7:00 p.m. – 9:30 p.m.</th></tr></thead>
+<tbody>
+<tr><td>Friday, Dec. 8</td><td>10:15 a.m. MW</td><td>Common:<br>PY 211</td></tr>
+<tr><td>Monday, Dec. 11</td><td>10:15 a.m. WF</td><td></td></tr>
+</tbody></table>
+<h2>Spring 2024 Exam Calendar</h2>
+<table><thead><tr><th>Exam Dates/Times</th><th>8:30 a.m. – 11:00 a.m.</th></tr></thead>
+<tbody><tr><td>Monday, April 29</td><td>10:15 a.m. MW</td></tr></tbody></table>"#;
+
+    fn snapshot() -> Snapshot {
+        Snapshot::new(CalendarMap::from_html_str(PAGE).unwrap().0).unwrap()
+    }
+
+    fn get(target: &str) -> (u16, Value) {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let reply = route(path, query, &snapshot());
+        (reply.status, serde_json::from_str(&reply.body).unwrap())
+    }
+
+    #[test]
+    fn percent_decode_handles_escapes_and_plus() {
+        assert_eq!(percent_decode("Fall%202023"), "Fall 2023");
+        assert_eq!(percent_decode("PY+211"), "PY 211");
+        assert_eq!(percent_decode("7%3A30%E2%80%938%3A55"), "7:30–8:55");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%2"), "%zz%2");
+    }
+
+    #[test]
+    fn route_serves_semesters_and_whole_map_bodies() {
+        let snapshot = snapshot();
+        assert_eq!(
+            get("/semesters"),
+            (200, serde_json::json!(["Fall 2023", "Spring 2024"]))
+        );
+        assert_eq!(get("/semesters/").0, 200);
+        assert_eq!(get("/semesters/Fall%202023").0, 200);
+        assert_eq!(get("/semesters/Fall%202030").0, 404);
+        assert_eq!(get("/semesters/Winter%202024").0, 400);
+        assert_eq!(get("/nope").0, 404);
+
+        let json = route("/exams.json", "", &snapshot);
+        assert_eq!(
+            (json.content_type, json.body),
+            (JSON, snapshot.json.clone())
+        );
+        let ics = route("/exams.ics", "", &snapshot);
+        assert_eq!((ics.content_type, ics.body), (ICS, snapshot.ics.clone()));
+    }
+
+    #[test]
+    fn lookup_by_meeting_or_name() {
+        let (status, body) = get("/lookup?days=MW&time=10%3A15am&semester=Fall%202023");
+        assert_eq!(status, 200);
+        assert_eq!(body["listed"], "MW 10:15 AM");
+        assert_eq!(body["match_kind"], "Exact");
+        assert_eq!(body["exams"][0]["date"], "2023-12-08");
+        assert_eq!(body["also"], serde_json::json!([]));
+
+        let (status, body) = get("/lookup?class=py+211&semester=Fall+2023");
+        assert_eq!(status, 200);
+        assert_eq!(body["listed"], "PY 211");
+        assert_eq!(body["exams"][0]["start"], "2023-12-08T19:00:00-05:00");
+
+        // The latest semester by default
+        let (_, body) = get("/lookup?days=MW&time=10:15am");
+        assert_eq!(body["semester"], "Spring 2024");
+    }
+
+    #[test]
+    fn lookup_reports_ties_and_bad_queries() {
+        let (status, body) = get("/lookup?days=MWF&time=10:15am&semester=Fall%202023");
+        assert_eq!(status, 200);
+        assert_eq!(body["listed"], "MW 10:15 AM");
+        assert_eq!(body["also"][0]["listed"], "WF 10:15 AM");
+
+        assert_eq!(get("/lookup?days=MW").0, 400);
+        assert_eq!(get("/lookup?class=MW%2010%3A15&semester=Fall").0, 400);
+        assert_eq!(get("/lookup?class=CSC%20999").0, 404);
+        assert_eq!(get("/lookup?class=PY%20211&semester=Fall%202030").0, 404);
+    }
+}
@@ -0,0 +1,160 @@
+/*
+* Copyright (C) 2023 Bennett Petzold
+*
+* This file is part of ncsu_exam_calendar.
+*
+* ncsu_exam_calendar is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 2 of the License, or (at your option) any later version.
+*
+* ncsu_exam_calendar is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License along with ncsu_exam_calendar. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Snapshots and change notifications for `watch`.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use ncsu_cal_lib::{calendar::CalendarMap, diff::CalendarDiff, fetch::ReqwestFetcher};
+use serde_json::json;
+
+const SNAPSHOT_PREFIX: &str = "exams-";
+const SNAPSHOT_TIME: &str = "%Y%m%dT%H%M%SZ";
+
+/// A directory of calendars named by the time they were fetched.
+pub struct Snapshots {
+    dir: PathBuf,
+}
+
+impl Snapshots {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// The most recent snapshot, if there is one.
+    pub fn latest(&self) -> Result<Option<(PathBuf, CalendarMap)>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context(format!("Could not read {}", self.dir.display())),
+        };
+        // The timestamps sort the same as the names
+        let latest = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| {
+                        name.starts_with(SNAPSHOT_PREFIX) && name.ends_with(".json")
+                    })
+            })
+            .max();
+
+        let Some(path) = latest else {
+            return Ok(None);
+        };
+        let cals = serde_json::from_reader(BufReader::new(File::open(&path)?))
+            .with_context(|| format!("{} is not a calendar JSON file", path.display()))?;
+        Ok(Some((path, cals)))
+    }
+
+    pub fn save(&self, cals: &CalendarMap, time: DateTime<Utc>) -> Result<PathBuf> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Could not create {}", self.dir.display()))?;
+        let path = self.dir.join(format!(
+            "{SNAPSHOT_PREFIX}{}.json",
+            time.format(SNAPSHOT_TIME)
+        ));
+        fs::write(&path, serde_json::to_string_pretty(cals)?)
+            .with_context(|| format!("Could not write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// What to do when the calendar changes.
+pub struct Actions {
+    /// Run with `sh -c`, given the change as JSON on stdin.
+    pub exec: Option<String>,
+    /// Receives the change as a JSON POST.
+    pub webhook: Option<String>,
+    /// Gets each change appended as text.
+    pub changelog: Option<PathBuf>,
+    /// Sends the webhook, with the same settings as page fetches.
+    pub client: ReqwestFetcher,
+}
+
+impl Actions {
+    /// Runs every configured action, returning the ones that failed.
+    pub async fn run(
+        &self,
+        url: &str,
+        time: DateTime<Utc>,
+        snapshot: &Path,
+        diff: &CalendarDiff,
+    ) -> Vec<anyhow::Error> {
+        let payload = json!({
+            "url": url,
+            "checked_at": time.to_rfc3339(),
+            "snapshot": snapshot,
+            "diff": diff,
+        })
+        .to_string();
+
+        let mut errors = vec![];
+        if let Some(changelog) = &self.changelog {
+            if let Err(e) = append_changelog(changelog, time, diff) {
+                errors.push(e);
+            }
+        }
+        if let Some(command) = &self.exec {
+            if let Err(e) = exec(command, &payload) {
+                errors.push(e);
+            }
+        }
+        if let Some(webhook) = &self.webhook {
+            if let Err(e) = self.client.post_json(webhook, payload).await {
+                errors.push(e.context(format!("Webhook {webhook} failed")));
+            }
+        }
+        errors
+    }
+}
+
+fn append_changelog(path: &Path, time: DateTime<Utc>, diff: &CalendarDiff) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Could not open {}", path.display()))?;
+    writeln!(file, "## {}\n{diff}", time.to_rfc3339())
+        .with_context(|| format!("Could not write {}", path.display()))
+}
+
+fn exec(command: &str, payload: &str) -> Result<()> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut child = Command::new(shell)
+        .args([flag, command])
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Could not run \"{command}\""))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A command that ignores its input is fine
+        let _ = stdin.write_all(payload.as_bytes());
+    }
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!("\"{command}\" exited with {status}"))
+    }
+}
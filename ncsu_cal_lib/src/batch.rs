@@ -0,0 +1,199 @@
+/*
+* Copyright (C) 2023 Bennett Petzold
+*
+* This file is part of ncsu_exam_calendar.
+*
+* ncsu_exam_calendar is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 2 of the License, or (at your option) any later version.
+*
+* ncsu_exam_calendar is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License along with ncsu_exam_calendar. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::io::{Read, Write};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::calendar::{Calendar, Class, MeetingMatch};
+
+/// Columns read from the input, matched case-insensitively. Any other columns are copied through.
+pub const INPUT_COLUMNS: [&str; 3] = ["course", "days", "start"];
+
+/// Columns appended to the output.
+pub const OUTPUT_COLUMNS: [&str; 6] = [
+    "exam_date",
+    "exam_start",
+    "exam_end",
+    "match_kind",
+    "note",
+    "error",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchSummary {
+    pub rows: usize,
+    pub unresolved: usize,
+    /// Sections that matched several listings equally well.
+    pub ambiguous: usize,
+}
+
+/// Finds the exams for one section.
+///
+/// A course listed by name (a common exam) wins over its meeting time, otherwise the meeting is
+/// resolved like [`Calendar::resolve`].
+pub fn resolve_section<'a>(
+    calendar: &'a Calendar,
+    course: &str,
+    days: &str,
+    start: &str,
+) -> Result<MeetingMatch<'a>> {
+    if !course.trim().is_empty() {
+        if let Some(found) = calendar.resolve(&Class::Name(course.trim().to_string())) {
+            return Ok(found);
+        }
+    }
+    if days.trim().is_empty() && start.trim().is_empty() {
+        bail!("No meeting time, and the course has no common exam")
+    }
+
+    let class: Class = format!("{} {}", days.trim(), start.trim()).parse()?;
+    if let Class::Name(_) = class {
+        bail!("\"{days}\" at \"{start}\" is not a meeting time")
+    }
+    calendar
+        .resolve(&class)
+        .ok_or_else(|| anyhow!("No exam listed for {class}"))
+}
+
+/// Reads sections from `input` and writes them to `output` with their exams.
+///
+/// Sections with several exams get one output row per exam. Unresolved sections keep their row,
+/// with the reason in the `error` column, as do rows with more fields than the header. Ambiguous
+/// matches are explained in the `note` column.
+pub fn assign_exams<R: Read, W: Write>(
+    calendar: &Calendar,
+    input: R,
+    output: W,
+) -> Result<BatchSummary> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(input);
+    let mut writer = csv::Writer::from_writer(output);
+
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header.trim().eq_ignore_ascii_case(name))
+            .ok_or_else(|| anyhow!("Missing \"{name}\" column"))
+    };
+    let [course, days, start] = [
+        column(INPUT_COLUMNS[0])?,
+        column(INPUT_COLUMNS[1])?,
+        column(INPUT_COLUMNS[2])?,
+    ];
+
+    let mut out_headers = headers.clone();
+    out_headers.extend(OUTPUT_COLUMNS);
+    writer.write_record(&out_headers)?;
+
+    let mut summary = BatchSummary::default();
+    for record in reader.records() {
+        let mut record = record?;
+        let extra = record.len().saturating_sub(headers.len());
+        // Keep the appended columns lined up with the header
+        record.truncate(headers.len());
+        while record.len() < headers.len() {
+            record.push_field("");
+        }
+        let field = |idx: usize| record.get(idx).unwrap_or_default();
+        summary.rows += 1;
+
+        let found = if extra > 0 {
+            Err(anyhow!(
+                "{extra} more fields than the header, check for unquoted commas"
+            ))
+        } else {
+            resolve_section(calendar, field(course), field(days), field(start))
+        };
+        match found {
+            Ok(found) => {
+                let note = found.ambiguity().unwrap_or_default();
+                if found.is_ambiguous() {
+                    summary.ambiguous += 1;
+                }
+                for exam in found.exams {
+                    let mut row = record.clone();
+                    row.push_field(&exam.date.format("%Y-%m-%d").to_string());
+                    row.push_field(&exam.time.start.format("%H:%M").to_string());
+                    row.push_field(&exam.time.end.format("%H:%M").to_string());
+                    row.push_field(found.kind.name());
+                    row.push_field(&note);
+                    row.push_field("");
+                    writer.write_record(&row)?;
+                }
+            }
+            Err(e) => {
+                summary.unresolved += 1;
+                let mut row = record.clone();
+                row.extend(["", "", "", "", ""]);
+                row.push_field(&e.to_string());
+                writer.write_record(&row)?;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveTime};
+
+    use super::*;
+    use crate::calendar::{Exam, MatchKind, Weekday};
+
+    #[test]
+    fn long_rows_are_rejected_and_columns_stay_aligned() {
+        let time = |hour, minute| NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+        let calendar = Calendar::from_iter([(
+            Class::Time(vec![Weekday::Monday, Weekday::Wednesday], time(10, 15)),
+            Exam::new(
+                NaiveDate::from_ymd_opt(2023, 12, 8).unwrap(),
+                time(8, 30)..time(11, 0),
+            ),
+        )]);
+        let input = "Course,Days,Start\nCSC 316,MW,10:15 AM\nCSC 316,MW,10:15 AM,extra\n";
+
+        let mut output = Vec::new();
+        let summary = assign_exams(&calendar, input.as_bytes(), &mut output).unwrap();
+        assert_eq!(summary.rows, 2);
+        assert_eq!(summary.unresolved, 1);
+
+        let output = String::from_utf8(output).unwrap();
+        let rows: Vec<_> = output.lines().collect();
+        assert_eq!(
+            rows[..2],
+            [
+                "Course,Days,Start,exam_date,exam_start,exam_end,match_kind,note,error",
+                "CSC 316,MW,10:15 AM,2023-12-08,08:30,11:00,Exact,,",
+            ]
+        );
+        assert!(
+            rows[2].starts_with("CSC 316,MW,10:15 AM,,,,,,"),
+            "{}",
+            rows[2]
+        );
+    }
+
+    #[test]
+    fn match_kind_names_are_the_serde_names() {
+        for kind in [
+            MatchKind::Exact,
+            MatchKind::DaysSubset,
+            MatchKind::DaysSuperset,
+            MatchKind::Range,
+            MatchKind::Name,
+        ] {
+            assert_eq!(serde_json::to_value(kind).unwrap(), kind.name());
+        }
+    }
+}
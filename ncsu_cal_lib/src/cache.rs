@@ -0,0 +1,327 @@
+/*
+* Copyright (C) 2023 Bennett Petzold
+*
+* This file is part of ncsu_exam_calendar.
+*
+* ncsu_exam_calendar is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 2 of the License, or (at your option) any later version.
+*
+* ncsu_exam_calendar is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License along with ncsu_exam_calendar. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! The last fetched exam page, kept on disk so unchanged pages are not downloaded or parsed again.
+
+use std::{
+    fmt::Display,
+    fs::{self, File},
+    io::BufReader,
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Local, Utc};
+use select::document::Document;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    calendar::{CalendarMap, ParseReport},
+    fetch::{Fetched, PageFetcher, Validators},
+};
+
+const PAGE_FILE: &str = "page.html";
+const PARSED_FILE: &str = "parsed.json";
+const META_FILE: &str = "meta.json";
+
+/// Where and when the cached page came from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheMeta {
+    pub url: String,
+    /// Last time the page was downloaded.
+    pub fetched_at: DateTime<Utc>,
+    /// Last time the server confirmed the page had not changed.
+    pub checked_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub validators: Validators,
+}
+
+impl Display for CacheMeta {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const FORMAT: &str = "%Y-%m-%d %H:%M";
+        write!(
+            f,
+            "Fetched {} from {}",
+            self.fetched_at.with_timezone(&Local).format(FORMAT),
+            self.url
+        )?;
+        if self.checked_at != self.fetched_at {
+            write!(
+                f,
+                ", unchanged as of {}",
+                self.checked_at.with_timezone(&Local).format(FORMAT)
+            )?;
+        }
+        match (&self.validators.etag, &self.validators.last_modified) {
+            (Some(etag), _) => write!(f, " (ETag {etag})"),
+            (None, Some(date)) => write!(f, " (Last-Modified {date})"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedCalendars {
+    pub calendars: CalendarMap,
+    pub report: ParseReport,
+    pub meta: CacheMeta,
+    /// The server answered 304 Not Modified.
+    pub from_cache: bool,
+}
+
+/// A directory holding one exam page, its parse, and [`CacheMeta`].
+#[derive(Debug, Clone)]
+pub struct PageCache {
+    dir: PathBuf,
+}
+
+impl PageCache {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn meta(&self) -> Option<CacheMeta> {
+        let file = File::open(self.dir.join(META_FILE)).ok()?;
+        serde_json::from_reader(BufReader::new(file)).ok()
+    }
+
+    /// Calendars from `url`, only downloaded and parsed if the page changed
+    /// since it was cached.
+    pub async fn get_calendars<F: PageFetcher, S: AsRef<str>>(
+        &self,
+        fetcher: &F,
+        url: S,
+    ) -> Result<CachedCalendars> {
+        let url = url.as_ref();
+        let known = self
+            .meta()
+            .filter(|meta| meta.url == url && !meta.validators.is_empty());
+
+        let fetched = fetcher
+            .fetch_if_changed(url, known.as_ref().map(|meta| &meta.validators))
+            .await?;
+        let (body, validators) = match (fetched, known) {
+            (Fetched::Page { body, validators }, _) => (body, validators),
+            (Fetched::NotModified, Some(mut meta)) => match self.read_parsed() {
+                Ok((calendars, report)) => {
+                    meta.checked_at = Utc::now();
+                    self.write_meta(&meta);
+                    return Ok(CachedCalendars {
+                        calendars,
+                        report,
+                        meta,
+                        from_cache: true,
+                    });
+                }
+                Err(e) => {
+                    log::warn!("Downloading again, the page cache is unreadable: {e:#}");
+                    (fetcher.fetch(url).await?, Validators::default())
+                }
+            },
+            (Fetched::NotModified, None) => {
+                return Err(anyhow!("{url} answered Not Modified to a plain request"))
+            }
+        };
+
+        let (calendars, report) = CalendarMap::parse(&Document::from(body.as_str()))?;
+        let now = Utc::now();
+        let meta = CacheMeta {
+            url: url.to_string(),
+            fetched_at: now,
+            checked_at: now,
+            validators,
+        };
+        if let Err(e) = self.store(&body, &calendars, &report, &meta) {
+            log::warn!("Could not update the page cache: {e:#}");
+        }
+        Ok(CachedCalendars {
+            calendars,
+            report,
+            meta,
+            from_cache: false,
+        })
+    }
+
+    fn read_parsed(&self) -> Result<(CalendarMap, ParseReport)> {
+        let path = self.dir.join(PARSED_FILE);
+        let parsed = File::open(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| Ok(serde_json::from_reader(BufReader::new(file))?));
+        match parsed {
+            Ok(parsed) => Ok(parsed),
+            // Older or damaged parse, the page itself may still be fine
+            Err(_) => CalendarMap::from_reader(File::open(self.dir.join(PAGE_FILE))?),
+        }
+    }
+
+    fn store(
+        &self,
+        page: &str,
+        calendars: &CalendarMap,
+        report: &ParseReport,
+        meta: &CacheMeta,
+    ) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Could not create {}", self.dir.display()))?;
+        fs::write(self.dir.join(PAGE_FILE), page)?;
+        fs::write(
+            self.dir.join(PARSED_FILE),
+            serde_json::to_string(&(calendars, report))?,
+        )?;
+        fs::write(
+            self.dir.join(META_FILE),
+            serde_json::to_string_pretty(meta)?,
+        )?;
+        Ok(())
+    }
+
+    fn write_meta(&self, meta: &CacheMeta) {
+        let written = serde_json::to_string_pretty(meta)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(fs::write(self.dir.join(META_FILE), json)?));
+        if let Err(e) = written {
+            log::warn!("Could not update the page cache: {e:#}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    const PAGE: &str = r#"<h2>Fall 2023 Exam Calendar</h2>
+<table><thead><tr><th>Exam Dates/Times</th><th>8:30 a.m. – 11:00 a.m.</th></tr></thead>
+<tbody><tr><td>Friday, Dec. 8</td><td>10:15 a.m. MW</td></tr></tbody></table>"#;
+    const URL: &str = "https://example.edu/calendars/exam-calendar/";
+
+    /// Answers Not Modified to its own ETag, and records the validators it was sent.
+    struct StubFetcher {
+        etag: &'static str,
+        sent: RefCell<Vec<Option<Validators>>>,
+    }
+
+    impl StubFetcher {
+        fn new(etag: &'static str) -> Self {
+            Self {
+                etag,
+                sent: RefCell::new(vec![]),
+            }
+        }
+    }
+
+    impl PageFetcher for StubFetcher {
+        async fn fetch(&self, _url: &str) -> Result<String> {
+            Ok(PAGE.to_string())
+        }
+
+        async fn probe(&self, _url: &str) -> bool {
+            true
+        }
+
+        async fn fetch_if_changed(
+            &self,
+            _url: &str,
+            known: Option<&Validators>,
+        ) -> Result<Fetched> {
+            self.sent.borrow_mut().push(known.cloned());
+            if known.and_then(|known| known.etag.as_deref()) == Some(self.etag) {
+                return Ok(Fetched::NotModified);
+            }
+            Ok(Fetched::Page {
+                body: PAGE.to_string(),
+                validators: Validators {
+                    etag: Some(self.etag.to_string()),
+                    last_modified: None,
+                },
+            })
+        }
+    }
+
+    fn cache(name: &str) -> PageCache {
+        let dir = std::env::temp_dir().join(format!(
+            "ncsu_exam_page_cache_{name}_{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        PageCache::new(dir)
+    }
+
+    #[tokio::test]
+    async fn not_modified_returns_the_cached_parse() {
+        let cache = cache("not_modified");
+        let fetcher = StubFetcher::new("\"v1\"");
+
+        let first = cache.get_calendars(&fetcher, URL).await.unwrap();
+        assert!(!first.from_cache);
+        let second = cache.get_calendars(&fetcher, URL).await.unwrap();
+        fs::remove_dir_all(&cache.dir).unwrap();
+
+        assert!(second.from_cache);
+        assert_eq!(second.calendars, first.calendars);
+        assert_eq!(second.report, first.report);
+        assert_eq!(second.meta.fetched_at, first.meta.fetched_at);
+        assert!(second.meta.checked_at >= first.meta.checked_at);
+        assert_eq!(
+            *fetcher.sent.borrow(),
+            [None, Some(first.meta.validators.clone())]
+        );
+    }
+
+    #[tokio::test]
+    async fn unreadable_parse_falls_back_to_the_page() {
+        let cache = cache("unreadable");
+        let fetcher = StubFetcher::new("\"v1\"");
+
+        let first = cache.get_calendars(&fetcher, URL).await.unwrap();
+        fs::write(cache.dir.join(PARSED_FILE), "not json").unwrap();
+        let second = cache.get_calendars(&fetcher, URL).await;
+        fs::remove_dir_all(&cache.dir).unwrap();
+
+        let second = second.unwrap();
+        assert!(second.from_cache);
+        assert_eq!(second.calendars, first.calendars);
+    }
+
+    #[tokio::test]
+    async fn unreadable_cache_downloads_again() {
+        let cache = cache("damaged");
+        let fetcher = StubFetcher::new("\"v1\"");
+
+        let first = cache.get_calendars(&fetcher, URL).await.unwrap();
+        fs::write(cache.dir.join(PARSED_FILE), "not json").unwrap();
+        fs::remove_file(cache.dir.join(PAGE_FILE)).unwrap();
+        let second = cache.get_calendars(&fetcher, URL).await;
+        fs::remove_dir_all(&cache.dir).unwrap();
+
+        let second = second.unwrap();
+        assert!(!second.from_cache);
+        assert_eq!(second.calendars, first.calendars);
+    }
+
+    #[tokio::test]
+    async fn new_url_ignores_old_validators() {
+        let cache = cache("new_url");
+        let fetcher = StubFetcher::new("\"v1\"");
+        let other = "https://example.edu/calendars/other/";
+
+        cache.get_calendars(&fetcher, URL).await.unwrap();
+        let second = cache.get_calendars(&fetcher, other).await.unwrap();
+        let meta = cache.meta();
+        fs::remove_dir_all(&cache.dir).unwrap();
+
+        assert!(!second.from_cache);
+        assert_eq!(*fetcher.sent.borrow(), [None, None]);
+        assert_eq!(meta.unwrap().url, other);
+    }
+}
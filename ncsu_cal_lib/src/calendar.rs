@@ -11,13 +11,17 @@
 */
 
 use std::{
-    collections::HashMap,
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    io::Read,
     ops::{Deref, Range},
     str::FromStr,
 };
 
 use anyhow::{anyhow, bail, Result};
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike};
+use chrono_tz::Tz;
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -26,14 +30,19 @@ use select::{
     node::Node,
     predicate::{Any, Name},
 };
-use serde::{de::Visitor, Deserialize, Serialize};
-use strum::EnumString;
+use serde::{
+    de::{value::MapAccessDeserializer, MapAccess, SeqAccess, Visitor},
+    ser::SerializeStruct,
+    Deserialize, Serialize,
+};
 
-use crate::get_page_document;
+use crate::{
+    fetch::{PageFetcher, ReqwestFetcher},
+    get_page_document_with,
+    semester::Semester,
+};
 
-#[derive(
-    Debug, PartialEq, Eq, Hash, Serialize, Deserialize, EnumString, Clone, PartialOrd, Ord,
-)]
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Clone, PartialOrd, Ord)]
 pub enum Weekday {
     Monday,
     Tuesday,
@@ -84,6 +93,92 @@ impl Weekday {
             x => bail!("{x} is not a weekday"),
         }
     }
+
+    /// The full name, as read by [`Weekday::from_name`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Monday => "Monday",
+            Self::Tuesday => "Tuesday",
+            Self::Wednesday => "Wednesday",
+            Self::Thursday => "Thursday",
+            Self::Friday => "Friday",
+        }
+    }
+
+    /// Reads any mix of weekday names and abbreviations, e.g. "MWF", "TuTh", "TR", "Mon/Wed" or
+    /// "monday wednesday", in any case.
+    ///
+    /// The longest name or abbreviation is taken at each step, so "TTh" is Tuesday, Thursday.
+    pub fn parse_days<S: AsRef<str>>(value: S) -> Result<Vec<Self>> {
+        let lower = value.as_ref().to_lowercase();
+        let mut rest = lower.trim_start_matches(DAY_SEPARATORS);
+        let mut days = Vec::new();
+        while !rest.is_empty() {
+            let (token, day) = DAY_TOKENS
+                .iter()
+                .find(|(token, _)| rest.starts_with(token))
+                .ok_or_else(|| anyhow!("\"{}\" is not a list of weekdays", value.as_ref()))?;
+            days.push(day.clone());
+            rest = rest[token.len()..].trim_start_matches(DAY_SEPARATORS);
+        }
+        if days.is_empty() {
+            bail!("No weekdays given")
+        }
+        Ok(days.into_iter().sorted().dedup().collect())
+    }
+}
+
+const DAY_SEPARATORS: [char; 4] = [' ', ',', '/', '&'];
+
+/// Names and abbreviations, longest first.
+const DAY_TOKENS: [(&str, Weekday); 23] = [
+    ("wednesday", Weekday::Wednesday),
+    ("thursday", Weekday::Thursday),
+    ("tuesday", Weekday::Tuesday),
+    ("monday", Weekday::Monday),
+    ("friday", Weekday::Friday),
+    ("thurs", Weekday::Thursday),
+    ("tues", Weekday::Tuesday),
+    ("thur", Weekday::Thursday),
+    ("mon", Weekday::Monday),
+    ("tue", Weekday::Tuesday),
+    ("wed", Weekday::Wednesday),
+    ("thu", Weekday::Thursday),
+    ("fri", Weekday::Friday),
+    ("mo", Weekday::Monday),
+    ("tu", Weekday::Tuesday),
+    ("we", Weekday::Wednesday),
+    ("th", Weekday::Thursday),
+    ("fr", Weekday::Friday),
+    ("m", Weekday::Monday),
+    ("t", Weekday::Tuesday),
+    ("w", Weekday::Wednesday),
+    ("r", Weekday::Thursday),
+    ("f", Weekday::Friday),
+];
+
+/// A single weekday, by full name or abbreviation ("Thursday", "Thu", "Th" or "R").
+impl FromStr for Weekday {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match Self::parse_days(s)?[..] {
+            [ref day] => Ok(day.clone()),
+            _ => bail!("\"{s}\" is more than one weekday"),
+        }
+    }
+}
+
+/// The abbreviation used on the exam page ("M", "Tu", "W", "Th", "F").
+impl Display for Weekday {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Monday => "M",
+            Self::Tuesday => "Tu",
+            Self::Wednesday => "W",
+            Self::Thursday => "Th",
+            Self::Friday => "F",
+        })
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
@@ -93,61 +188,76 @@ pub enum Class {
     Name(String),
 }
 
+/// Orders by kind (time, range, then name), then by time.
+impl Ord for Class {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Time(days, time), Self::Time(other_days, other_time)) => {
+                (time, days).cmp(&(other_time, other_days))
+            }
+            (Self::Range(range), Self::Range(other_range)) => {
+                (range.start, range.end).cmp(&(other_range.start, other_range.end))
+            }
+            (Self::Name(name), Self::Name(other_name)) => name.cmp(other_name),
+            _ => self.kind_order().cmp(&other.kind_order()),
+        }
+    }
+}
+
+impl PartialOrd for Class {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 lazy_static! {
     static ref CLASS_ENTRY: Regex =
-        Regex::new(r"(\d{1,2}:\d{1,2} (?:a|p)\.m\.).*(:?M|Tu|W|Th|F)").unwrap();
+        Regex::new(r"(\d{1,2}:\d{1,2}\s*(?:a|p)\.m\.).*(:?M|Tu|W|Th|F)").unwrap();
 }
 
 impl Class {
-    pub fn factory<S: AsRef<str>>(value: S) -> Result<Vec<Self>> {
+    fn kind_order(&self) -> u8 {
+        match self {
+            Self::Time(..) => 0,
+            Self::Range(_) => 1,
+            Self::Name(_) => 2,
+        }
+    }
+
+    fn is_label<S: AsRef<str>>(value: S) -> bool {
         let value = value.as_ref().trim();
-        if value.is_empty() || value == "Common:" {
+        value.is_empty() || value == "Common:"
+    }
+
+    pub fn factory<S: AsRef<str>>(value: S) -> Result<Vec<Self>> {
+        if Self::is_label(&value) {
             bail!("Non-class line")
         }
+        let value = value.as_ref().trim();
 
         let caps = CLASS_ENTRY.captures(value);
         if let Some(caps) = caps {
-            let time = dateparser::parse_with_timezone::<Utc>(
-                &caps[1].replace('.', ""),
-                &Utc::now().timezone(),
-            )?
-            .time();
+            let time = parse_time(&caps[1].replace('.', ""))?;
             let weekdays = Weekday::factory(value)?;
             Ok(weekdays
                 .into_iter()
                 .map(|day| Self::Time(day, time))
                 .collect())
-        } else if let Some((start, end)) = value.split(&['-', '–']).map(str::trim).collect_tuple()
-        {
-            let start = dateparser::parse_with_timezone::<Utc>(
+        } else if let Some((start, end)) = value.split(DASHES).map(str::trim).collect_tuple() {
+            Ok(vec![Self::Range(parse_time_range(
                 &start.replace('.', ""),
-                &Utc::now().timezone(),
-            )?
-            .time();
-            let end = dateparser::parse_with_timezone::<Utc>(
                 &end.replace('.', ""),
-                &Utc::now().timezone(),
-            )?
-            .time();
-            Ok(vec![Self::Range(Range { start, end })])
+            )?)])
         } else if let Some((start, end)) = value.split("and").map(str::trim).collect_tuple() {
-            let start = dateparser::parse_with_timezone::<Utc>(
-                &start.replace('.', ""),
-                &Utc::now().timezone(),
-            )?
-            .time();
-
             let end = if end.trim().to_lowercase() == "later" {
                 "11:59 p.m."
             } else {
                 end
             };
-            let end = dateparser::parse_with_timezone::<Utc>(
+            Ok(vec![Self::Range(parse_time_range(
+                &start.replace('.', ""),
                 &end.replace('.', ""),
-                &Utc::now().timezone(),
-            )?
-            .time();
-            Ok(vec![Self::Range(Range { start, end })])
+            )?)])
         } else {
             Ok(value
                 .split(',')
@@ -159,28 +269,149 @@ impl Class {
     }
 }
 
+/// Reads a class the way a student would type it: "MWF 10:15am", "TuTh 1:30 pm",
+/// "7:30-8:55", "10:15 a.m. TuTh" or "CSC 316". Everything [`Class`]'s `Display` writes reads back.
+///
+/// Times without a.m. or p.m. before 7:00 are taken to be in the afternoon.
+impl FromStr for Class {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let value = s.trim();
+        if value.is_empty() {
+            bail!("Empty class")
+        }
+
+        if let Some((start, end)) = value.split(DASHES).map(str::trim).collect_tuple() {
+            if let (Ok(start), Ok(end)) = (parse_class_time(start), parse_class_time(end)) {
+                return Ok(Self::Range(Range { start, end }));
+            }
+        }
+
+        // Days before the time, then days after it
+        let words: Vec<_> = value.split_whitespace().collect();
+        let splits = (1..words.len())
+            .rev()
+            .map(|idx| (&words[..idx], &words[idx..]))
+            .chain((1..words.len()).map(|idx| (&words[idx..], &words[..idx])));
+        for (days, time) in splits {
+            if let (Ok(days), Ok(time)) = (
+                Weekday::parse_days(days.join(" ")),
+                parse_class_time(&time.join(" ")),
+            ) {
+                return Ok(Self::Time(days, time));
+            }
+        }
+
+        if parse_class_time(value).is_ok() {
+            bail!("\"{value}\" needs meeting days, e.g. \"MWF {value}\"")
+        }
+        Ok(Self::Name(value.to_string()))
+    }
+}
+
+/// "MWF 10:15 AM", "7:30 AM – 8:55 AM", or the course name.
+impl Display for Class {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Time(days, time) => {
+                for day in days {
+                    write!(f, "{day}")?;
+                }
+                write!(f, " {}", time.format(TIME_FORMAT))
+            }
+            Self::Range(range) => write!(
+                f,
+                "{} – {}",
+                range.start.format(TIME_FORMAT),
+                range.end.format(TIME_FORMAT)
+            ),
+            Self::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+/// [`parse_time`], reading bare hours before 7 as p.m. since no class meets that early.
+fn parse_class_time(value: &str) -> Result<NaiveTime> {
+    let time = parse_time(value)?;
+    if !value.chars().any(char::is_alphabetic) && (1..7).contains(&time.hour()) {
+        Ok(time + Duration::hours(12))
+    } else {
+        Ok(time)
+    }
+}
+
+/// Version of the JSON written by [`CalendarMap`]'s `Serialize` implementation.
+///
+/// Version 2 is an object `{"schema_version": 2, "semesters": {...}}`, where each semester
+/// (e.g. `"Fall 2023"`) maps to a list of `{"class", "date", "start", "end"}` entries. Classes are
+/// tagged by `"kind"`:
+/// - `{"kind": "time", "days": ["Monday", "Wednesday"], "time": "11:45:00"}`
+/// - `{"kind": "range", "start": "07:30:00", "end": "08:55:00"}`
+/// - `{"kind": "name", "name": "PY 211"}`
+///
+/// Dates are ISO 8601 (`"2023-12-13"`) and times are ISO 8601 local times (`"12:00:00"`).
+///
+/// Version 1 (no `schema_version`) is still accepted when loading, and is migrated on the next
+/// write.
+pub const SCHEMA_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum ClassRepr {
+    Time { days: Vec<Weekday>, time: NaiveTime },
+    Range { start: NaiveTime, end: NaiveTime },
+    Name { name: String },
+}
+
+impl From<&Class> for ClassRepr {
+    fn from(value: &Class) -> Self {
+        match value {
+            Class::Time(days, time) => Self::Time {
+                days: days.clone(),
+                time: *time,
+            },
+            Class::Range(range) => Self::Range {
+                start: range.start,
+                end: range.end,
+            },
+            Class::Name(name) => Self::Name { name: name.clone() },
+        }
+    }
+}
+
+impl From<ClassRepr> for Class {
+    fn from(value: ClassRepr) -> Self {
+        match value {
+            ClassRepr::Time { days, time } => Self::Time(days, time),
+            ClassRepr::Range { start, end } => Self::Range(Range { start, end }),
+            ClassRepr::Name { name } => Self::Name(name),
+        }
+    }
+}
+
 impl Serialize for Class {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        match self {
-            Self::Time(weekdays, time) => {
-                serializer.serialize_str(&format!("{:?} {:?}", weekdays, time))
-            }
-            Self::Range(range) => serializer.serialize_str(&format!("{:?}", range)),
-            Self::Name(name) => serializer.serialize_str(name),
-        }
+        ClassRepr::from(self).serialize(serializer)
     }
 }
 
 struct ClassVisitor;
 
-impl Visitor<'_> for ClassVisitor {
+impl<'de> Visitor<'de> for ClassVisitor {
     type Value = Class;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        formatter.write_str("\"WEEKDAYS TIME\", \"RANGE\", or NAME")
+        formatter.write_str("a tagged class object, or a v1 \"WEEKDAYS TIME\", \"RANGE\", or NAME")
+    }
+
+    fn visit_map<A>(self, map: A) -> std::result::Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        ClassRepr::deserialize(MapAccessDeserializer::new(map)).map(Class::from)
     }
 
     fn visit_str<E>(self, v: &str) -> std::result::Result<Self::Value, E>
@@ -197,18 +428,12 @@ impl Visitor<'_> for ClassVisitor {
                 .map(Weekday::from_str)
                 .collect::<Result<Vec<_>, _>>()
                 .map_err(|_| E::custom("Invalid weekday format"))?;
-            let time =
-                dateparser::parse_with_timezone::<Utc>(time_str.trim(), &Utc::now().timezone())
-                    .map_err(|_| E::custom("Invalid time format"))?
-                    .time();
+            let time = parse_time(time_str.trim()).map_err(|_| E::custom("Invalid time format"))?;
             Ok(Class::Time(days, time))
         } else if let Some((start_str, end_str)) = v.split("..").collect_tuple() {
-            let start = dateparser::parse_with_timezone::<Utc>(start_str, &Utc::now().timezone())
-                .map_err(|_| E::custom("Range begin format is invalid"))?
-                .time();
-            let end = dateparser::parse_with_timezone::<Utc>(end_str, &Utc::now().timezone())
-                .map_err(|_| E::custom("Range end format is invalid"))?
-                .time();
+            let start =
+                parse_time(start_str).map_err(|_| E::custom("Range begin format is invalid"))?;
+            let end = parse_time(end_str).map_err(|_| E::custom("Range end format is invalid"))?;
             Ok(Class::Range(Range { start, end }))
         } else {
             Ok(Class::Name(v.to_string()))
@@ -221,23 +446,583 @@ impl<'de> Deserialize<'de> for Class {
     where
         D: serde::Deserializer<'de>,
     {
-        deserializer.deserialize_str(ClassVisitor)
+        deserializer.deserialize_any(ClassVisitor)
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
-pub struct Calendar(HashMap<Class, (NaiveDate, Range<NaiveTime>)>);
+pub const DATE_FORMAT: &str = "%A, %B %e";
+pub const TIME_FORMAT: &str = "%-I:%M %p";
+
+/// Every time on the exam page is wall clock time on campus.
+pub const EXAM_TIMEZONE: Tz = chrono_tz::America::New_York;
+
+/// Hyphen, en dash and em dash, all used between the ends of a time range.
+const DASHES: [char; 3] = ['-', '–', '—'];
+
+lazy_static! {
+    static ref TIME_OF_DAY: Regex = Regex::new(
+        r"(?i)^(?:(?:12\s*)?(noon)|(?:12\s*)?(midnight)|(\d{1,2})(?::(\d{2}))?(?::(\d{2}))?(?:\s*(?:(a)|(p))\.?\s*m?\.?)?)$"
+    )
+    .unwrap();
+}
+
+/// Parses a time of day as written on the exam page.
+///
+/// Accepts "8:30 a.m.", "8:30am", "12 p.m.", "noon", "12 noon", "midnight" and 24 hour
+/// "13:05:00". Never consults the clock, so the same text always gives the same time.
+fn parse_time(value: &str) -> Result<NaiveTime> {
+    let value = value.replace(['\u{a0}', '\u{202f}'], " ");
+    let value = value.trim();
+    let caps = TIME_OF_DAY
+        .captures(value)
+        .ok_or_else(|| anyhow!("Not a time of day: \"{value}\""))?;
+
+    if caps.get(1).is_some() {
+        return Ok(NaiveTime::from_hms_opt(12, 0, 0).unwrap());
+    }
+    if caps.get(2).is_some() {
+        return Ok(NaiveTime::MIN);
+    }
+
+    let field = |idx| caps.get(idx).map_or(Ok(0), |x| x.as_str().parse::<u32>());
+    let (hour, minute, second) = (field(3)?, field(4)?, field(5)?);
+    let hour = match (caps.get(6).is_some(), caps.get(7).is_some()) {
+        (false, false) => hour,
+        _ if !(1..=12).contains(&hour) => bail!("Hour out of range in \"{value}\""),
+        (true, _) => hour % 12,
+        (_, true) => hour % 12 + 12,
+    };
+    NaiveTime::from_hms_opt(hour, minute, second)
+        .ok_or_else(|| anyhow!("Time out of range: \"{value}\""))
+}
+
+/// Parses both ends of a range like "1 – 3:30 p.m." or "9 p.m. – midnight".
+///
+/// A start hour without a.m. or p.m. takes the end's, unless that puts it after the end. A range
+/// ending at midnight ends at the end of the day, see [`Exam::end`].
+fn parse_time_range(start: &str, end: &str) -> Result<Range<NaiveTime>> {
+    let (start_time, end_time) = (parse_time(start)?, parse_time(end)?);
+    let start = match (meridiem(start), meridiem(end)) {
+        (Meridiem::None, Meridiem::Am | Meridiem::Pm) if (1..=12).contains(&start_time.hour()) => {
+            let am = start_time.with_hour(start_time.hour() % 12).unwrap();
+            let pm = am.with_hour(am.hour() + 12).unwrap();
+            if meridiem(end) == Meridiem::Pm && pm <= end_time {
+                pm
+            } else {
+                am
+            }
+        }
+        _ => start_time,
+    };
+    Ok(start..end_time)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Meridiem {
+    /// A bare or 24 hour time.
+    None,
+    Am,
+    Pm,
+    /// "noon" or "midnight".
+    Named,
+}
+
+fn meridiem(value: &str) -> Meridiem {
+    let value = value.replace(['\u{a0}', '\u{202f}'], " ");
+    match TIME_OF_DAY.captures(value.trim()) {
+        Some(caps) if caps.get(1).is_some() || caps.get(2).is_some() => Meridiem::Named,
+        Some(caps) if caps.get(6).is_some() => Meridiem::Am,
+        Some(caps) if caps.get(7).is_some() => Meridiem::Pm,
+        _ => Meridiem::None,
+    }
+}
+
+/// A single exam slot.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
+pub struct Exam {
+    pub date: NaiveDate,
+    pub time: Range<NaiveTime>,
+}
+
+impl Exam {
+    pub fn new(date: NaiveDate, time: Range<NaiveTime>) -> Self {
+        Self { date, time }
+    }
+
+    /// When the exam begins, in [`EXAM_TIMEZONE`].
+    pub fn start(&self) -> DateTime<Tz> {
+        localize(self.date, self.time.start)
+    }
+
+    /// When the exam ends, in [`EXAM_TIMEZONE`].
+    ///
+    /// An end at or before the start (e.g. 9 p.m. - midnight) is on the next day.
+    pub fn end(&self) -> DateTime<Tz> {
+        let date = if self.time.end <= self.time.start {
+            self.date.succ_opt().unwrap_or(self.date)
+        } else {
+            self.date
+        };
+        localize(date, self.time.end)
+    }
+}
+
+/// Resolves a wall clock time on campus to an instant.
+///
+/// Ambiguous times (fall back) take the earlier instant, and times skipped by
+/// spring forward are moved an hour later.
+fn localize(date: NaiveDate, time: NaiveTime) -> DateTime<Tz> {
+    let local = date.and_time(time);
+    EXAM_TIMEZONE
+        .from_local_datetime(&local)
+        .earliest()
+        .or_else(|| {
+            EXAM_TIMEZONE
+                .from_local_datetime(&(local + Duration::hours(1)))
+                .earliest()
+        })
+        .unwrap_or_else(|| EXAM_TIMEZONE.from_utc_datetime(&local))
+}
+
+impl PartialOrd for Exam {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Exam {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.date, self.time.start, self.time.end).cmp(&(
+            other.date,
+            other.time.start,
+            other.time.end,
+        ))
+    }
+}
+
+impl Display for Exam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, {} - {}",
+            self.date.format(DATE_FORMAT),
+            self.time.start.format(TIME_FORMAT),
+            self.time.end.format(TIME_FORMAT)
+        )
+    }
+}
+
+impl From<(NaiveDate, Range<NaiveTime>)> for Exam {
+    fn from((date, time): (NaiveDate, Range<NaiveTime>)) -> Self {
+        Self { date, time }
+    }
+}
+
+/// Every exam assigned to each class.
+///
+/// The page can list the same class in more than one cell, so a class may have several exams.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Calendar(HashMap<Class, Vec<Exam>>);
 
 impl Deref for Calendar {
-    type Target = HashMap<Class, (NaiveDate, Range<NaiveTime>)>;
+    type Target = HashMap<Class, Vec<Exam>>;
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
 
-impl TryFrom<(&str, Node<'_>)> for Calendar {
-    type Error = anyhow::Error;
-    fn try_from((year, value): (&str, Node)) -> std::result::Result<Self, Self::Error> {
+impl Calendar {
+    /// All exams for `class`, in chronological order.
+    pub fn exams(&self, class: &Class) -> &[Exam] {
+        self.get(class).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Every (class, exam) pair, ordered by class and then exam.
+    pub fn entries(&self) -> impl Iterator<Item = (&Class, &Exam)> {
+        self.iter()
+            .sorted_by_key(|(class, _)| *class)
+            .flat_map(|(class, exams)| exams.iter().map(move |exam| (class, exam)))
+    }
+
+    /// Classes listed with more than one exam.
+    pub fn duplicates(&self) -> impl Iterator<Item = (&Class, &Vec<Exam>)> {
+        self.iter().filter(|(_, exams)| exams.len() > 1)
+    }
+
+    /// Finds the exam for a class meeting on `days` at `start`.
+    ///
+    /// Prefers an exact listing, then listings whose weekdays contain (or are contained by) the
+    /// meeting's, and finally the narrowest listed time range containing `start`. Listings that
+    /// match equally well are returned in [`MeetingMatch::others`].
+    pub fn exam_for_meeting(&self, days: &[Weekday], start: NaiveTime) -> Option<MeetingMatch<'_>> {
+        let days: Vec<_> = days.iter().sorted().dedup().collect();
+
+        let candidates: Vec<_> = self
+            .iter()
+            .filter_map(|(class, exams)| {
+                let (kind, distance) = match class {
+                    Class::Time(listed, time) if *time == start && !days.is_empty() => {
+                        let listed: Vec<_> = listed.iter().sorted().dedup().collect();
+                        let distance = listed.len().abs_diff(days.len()) as i64;
+                        if listed == days {
+                            (MatchKind::Exact, distance)
+                        } else if days.iter().all(|day| listed.contains(day)) {
+                            (MatchKind::DaysSubset, distance)
+                        } else if listed.iter().all(|day| days.contains(day)) {
+                            (MatchKind::DaysSuperset, distance)
+                        } else {
+                            return None;
+                        }
+                    }
+                    // A range ending at or before its start runs to the end of the day
+                    Class::Range(range)
+                        if range.start <= start
+                            && (start <= range.end || range.end <= range.start) =>
+                    {
+                        let minutes = (range.end - range.start).num_minutes();
+                        (MatchKind::Range, minutes.rem_euclid(24 * 60))
+                    }
+                    _ => return None,
+                };
+                Some((kind, distance, class, exams.as_slice()))
+            })
+            .collect();
+
+        let best = candidates
+            .iter()
+            .map(|(kind, distance, ..)| (*kind, *distance))
+            .min()?;
+        let mut tied = candidates
+            .into_iter()
+            .filter(|(kind, distance, ..)| (*kind, *distance) == best)
+            .map(|(_, _, class, exams)| (class, exams))
+            .sorted_by_key(|(class, exams)| (exams.first(), *class));
+        let (class, exams) = tied.next()?;
+        Some(MeetingMatch {
+            class,
+            exams,
+            kind: best.0,
+            others: tied.collect(),
+        })
+    }
+
+    /// Finds the exams for `class`, falling back to [`Self::exam_for_meeting`] for meeting times
+    /// that are not listed exactly.
+    pub fn resolve(&self, class: &Class) -> Option<MeetingMatch<'_>> {
+        if let Some((class, exams)) = self.get_key_value(class) {
+            return Some(MeetingMatch {
+                class,
+                exams,
+                kind: MatchKind::Exact,
+                others: vec![],
+            });
+        }
+        match class {
+            Class::Time(days, time) => self.exam_for_meeting(days, *time),
+            Class::Range(range) => self.exam_for_meeting(&[], range.start),
+            Class::Name(name) => {
+                let wanted = normalize_name(name);
+                self.iter()
+                    .filter(|(listed, _)| {
+                        matches!(listed, Class::Name(listed) if normalize_name(listed) == wanted)
+                    })
+                    .min_by_key(|(listed, _)| *listed)
+                    .map(|(class, exams)| MeetingMatch {
+                        class,
+                        exams,
+                        kind: MatchKind::Name,
+                        others: vec![],
+                    })
+            }
+        }
+    }
+
+    /// Every class assigned to `exam`'s date and time.
+    pub fn classes_for_exam(&self, exam: &Exam) -> Vec<&Class> {
+        self.iter()
+            .filter(|(_, exams)| exams.contains(exam))
+            .map(|(class, _)| class)
+            .sorted()
+            .collect()
+    }
+
+    /// Reverse index of exam slot to the classes assigned to it, in chronological order.
+    pub fn by_exam(&self) -> BTreeMap<&Exam, Vec<&Class>> {
+        let mut index = BTreeMap::<_, Vec<_>>::new();
+        for (class, exam) in self.entries() {
+            index.entry(exam).or_default().push(class);
+        }
+        index.values_mut().for_each(|classes| classes.sort());
+        index
+    }
+
+    fn insert(&mut self, class: Class, exam: Exam) {
+        let exams = self.0.entry(class).or_default();
+        if let Err(idx) = exams.binary_search(&exam) {
+            exams.insert(idx, exam);
+        }
+    }
+}
+
+impl FromIterator<(Class, Exam)> for Calendar {
+    fn from_iter<T: IntoIterator<Item = (Class, Exam)>>(iter: T) -> Self {
+        let mut calendar = Self::default();
+        for (class, exam) in iter {
+            calendar.insert(class, exam);
+        }
+        calendar
+    }
+}
+
+/// How a meeting time was matched to a listed class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum MatchKind {
+    /// The class is listed with the same weekdays and start time.
+    Exact,
+    /// The meeting days are a subset of a listing at the same time (e.g. MW for MWF).
+    DaysSubset,
+    /// The meeting days are a superset of a listing at the same time (e.g. MWF for MW).
+    DaysSuperset,
+    /// The start time falls within a listed time range.
+    Range,
+    /// The course name matches, ignoring case and spaces.
+    Name,
+}
+
+impl MatchKind {
+    /// A stable identifier for files and scripts, the same as the serde form.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Exact => "Exact",
+            Self::DaysSubset => "DaysSubset",
+            Self::DaysSuperset => "DaysSuperset",
+            Self::Range => "Range",
+            Self::Name => "Name",
+        }
+    }
+}
+
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+impl Display for MatchKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Exact => "exact listing",
+            Self::DaysSubset => "listing on more days at the same time",
+            Self::DaysSuperset => "listing on fewer days at the same time",
+            Self::Range => "listed time range",
+            Self::Name => "course name, ignoring case and spaces",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MeetingMatch<'a> {
+    pub class: &'a Class,
+    pub exams: &'a [Exam],
+    pub kind: MatchKind,
+    /// Other listings that match just as well, e.g. both MW and WF for a MWF class. `class` is
+    /// the one with the earliest exam.
+    pub others: Vec<(&'a Class, &'a [Exam])>,
+}
+
+impl MeetingMatch<'_> {
+    pub fn is_ambiguous(&self) -> bool {
+        !self.others.is_empty()
+    }
+
+    /// Names the other listings, for callers to show when the match is ambiguous.
+    pub fn ambiguity(&self) -> Option<String> {
+        if self.others.is_empty() {
+            return None;
+        }
+        let others = self
+            .others
+            .iter()
+            .map(|(class, exams)| format!("{class} ({})", exams.iter().join("; ")))
+            .join(", ");
+        Some(format!(
+            "Also matches {others}, showing {} since its exam is first",
+            self.class
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CalendarEntry {
+    class: Class,
+    date: NaiveDate,
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl Serialize for Calendar {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_seq(self.entries().map(|(class, exam)| CalendarEntry {
+            class: class.clone(),
+            date: exam.date,
+            start: exam.time.start,
+            end: exam.time.end,
+        }))
+    }
+}
+
+struct CalendarVisitor;
+
+impl<'de> Visitor<'de> for CalendarVisitor {
+    type Value = Calendar;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a list of exam entries, or a v1 map of classes to exams")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut calendar = Calendar::default();
+        while let Some(entry) = seq.next_element::<CalendarEntry>()? {
+            calendar.insert(
+                entry.class,
+                Exam::new(
+                    entry.date,
+                    Range {
+                        start: entry.start,
+                        end: entry.end,
+                    },
+                ),
+            );
+        }
+        Ok(calendar)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> std::result::Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut calendar = Calendar::default();
+        while let Some((class, exam)) = map.next_entry::<Class, (NaiveDate, Range<NaiveTime>)>()? {
+            calendar.insert(class, exam.into());
+        }
+        Ok(calendar)
+    }
+}
+
+impl<'de> Deserialize<'de> for Calendar {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(CalendarVisitor)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SkipKind {
+    Table,
+    Row,
+    Cell,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Skipped {
+    pub kind: SkipKind,
+    pub semester: Option<String>,
+    pub text: String,
+    pub reason: String,
+}
+
+impl Display for Skipped {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{:?}] {}: \"{}\" ({})",
+            self.kind,
+            self.semester.as_deref().unwrap_or("<no semester>"),
+            self.text,
+            self.reason
+        )
+    }
+}
+
+/// Everything dropped while parsing the exam calendar page.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParseReport {
+    pub skipped: Vec<Skipped>,
+}
+
+impl ParseReport {
+    pub fn is_empty(&self) -> bool {
+        self.skipped.is_empty()
+    }
+
+    fn skip<T: ToString, R: ToString>(
+        &mut self,
+        kind: SkipKind,
+        semester: Option<&str>,
+        text: T,
+        reason: R,
+    ) {
+        self.skipped.push(Skipped {
+            kind,
+            semester: semester.map(str::to_string),
+            text: text.to_string().trim().to_string(),
+            reason: reason.to_string(),
+        })
+    }
+}
+
+impl Display for ParseReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for skipped in &self.skipped {
+            writeln!(f, "{skipped}")?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_exam_date(text: &str, year: &str) -> Result<NaiveDate> {
+    let exam_date = text.replace('.', "");
+    let exam_date = exam_date.replace(',', "");
+    let exam_date = exam_date.trim();
+    let exam_date = exam_date
+        .split_once(' ')
+        .ok_or(anyhow!(
+            "Unexpected exam_date format (no space): {exam_date}"
+        ))?
+        .1;
+    let exam_date = exam_date.to_string() + " " + year + " 00:00";
+
+    let (parse_1, parse_2) = (
+        NaiveDateTime::parse_from_str(&exam_date, "%b %d %Y %R"),
+        NaiveDateTime::parse_from_str(&exam_date, "%B %d %Y %R"),
+    );
+    let parsed = if let Ok(parse_1) = parse_1 {
+        parse_1
+    } else {
+        parse_2?
+    };
+    Ok(parsed.date())
+}
+
+impl Calendar {
+    /// Parses one exam table, recording skipped rows and cells in `report`.
+    ///
+    /// Errors mean the whole table is unusable.
+    pub fn parse(
+        semester: Option<&str>,
+        year: &str,
+        value: Node,
+        report: &mut ParseReport,
+    ) -> Result<Self> {
         let head_node = value
             .find(Name("thead"))
             .next()
@@ -254,26 +1039,12 @@ impl TryFrom<(&str, Node<'_>)> for Calendar {
         head_iter.next();
         let exam_times = head_iter
             .map(|time| {
-                let range = time
-                    .replace('.', "")
-                    .split('–')
-                    .map(str::trim)
-                    .map(|x| dateparser::parse_with_timezone(x, &Utc::now().timezone()))
-                    .collect_tuple();
-                match range {
-                    Some((Ok(first), Ok(second))) => Ok((first, second)),
-                    Some((Err(first), _)) => Err(first),
-                    Some((_, Err(second))) => Err(second),
+                let time = time.replace('.', "");
+                match time.split(DASHES).map(str::trim).collect_tuple() {
+                    Some((start, end)) => parse_time_range(start, end),
                     None => bail!("Not a valid range of times separated by \"-\""),
                 }
             })
-            .map(|range| match range {
-                Ok((first, second)) => Ok(Range {
-                    start: first.time(),
-                    end: second.time(),
-                }),
-                Err(range) => Err(range),
-            })
             .collect::<Result<Vec<_>>>()?;
 
         let exam_times: Vec<_> = exam_times.into_iter().unique().collect();
@@ -282,122 +1053,626 @@ impl TryFrom<(&str, Node<'_>)> for Calendar {
             .find(Name("tbody"))
             .next()
             .ok_or(anyhow!("No table body"))?;
-        let body_rows = body.find(Name("tr")).map(|row| row.find(Name("td")));
-        let assignments = body_rows
-            .map(|mut row| {
-                let exam_date = row
-                    .next()
-                    .ok_or(anyhow!("At least one row is empty"))?
-                    .text();
-                let exam_date = exam_date.replace('.', "");
-                let exam_date = exam_date.replace(',', "");
-                let exam_date = exam_date.trim();
-                let exam_date = exam_date
-                    .split_once(' ')
-                    .ok_or(anyhow!(
-                        "Unexpected exam_date format (no space): {exam_date}"
-                    ))?
-                    .1;
-                let exam_date = exam_date.to_string() + " " + year + " 00:00";
-
-                let (parse_1, parse_2) = (
-                    NaiveDateTime::parse_from_str(&exam_date, "%b %d %Y %R"),
-                    NaiveDateTime::parse_from_str(&exam_date, "%B %d %Y %R"),
-                );
-                let parsed = if let Ok(parse_1) = parse_1 {
-                    Ok(parse_1)
-                } else {
-                    parse_2
-                };
 
-                match parsed {
-                    Ok(exam_date) => Ok(row
-                        .zip(exam_times.clone())
-                        .flat_map(|(columns, exam_time)| {
-                            columns
-                                .children()
-                                .flat_map(|class| {
-                                    let classes = Class::factory(class.text())?;
-                                    Ok::<Vec<_>, anyhow::Error>(
-                                        classes
-                                            .into_iter()
-                                            .map(|inner_class| {
-                                                (inner_class, (exam_date.date(), exam_time.clone()))
-                                            })
-                                            .collect(),
-                                    )
-                                })
-                                .flatten()
-                                .collect::<Vec<_>>()
-                        })
-                        .collect::<Vec<_>>()),
-                    Err(exam_date) => Err(anyhow!(exam_date)),
+        let mut assignments = Vec::new();
+        for row in body.find(Name("tr")) {
+            let mut columns = row.find(Name("td"));
+            let Some(date_column) = columns.next() else {
+                report.skip(SkipKind::Row, semester, row.text(), "Row is empty");
+                continue;
+            };
+            let exam_date = match parse_exam_date(&date_column.text(), year) {
+                Ok(exam_date) => exam_date,
+                Err(e) => {
+                    report.skip(SkipKind::Row, semester, row.text(), e);
+                    continue;
                 }
-            })
-            .collect::<Result<Vec<_>>>()?;
+            };
 
-        Ok(Self(assignments.into_iter().flatten().collect()))
+            for (idx, column) in columns.enumerate() {
+                let exam_time = exam_times.get(idx);
+                for class in column.children() {
+                    let text = class.text();
+                    if Class::is_label(&text) {
+                        continue;
+                    }
+                    let Some(exam_time) = exam_time else {
+                        report.skip(
+                            SkipKind::Cell,
+                            semester,
+                            text,
+                            "No exam time column for cell",
+                        );
+                        continue;
+                    };
+                    match Class::factory(&text) {
+                        Ok(classes) => assignments.extend(classes.into_iter().map(|inner_class| {
+                            (inner_class, Exam::new(exam_date, exam_time.clone()))
+                        })),
+                        Err(e) => report.skip(SkipKind::Cell, semester, text, e),
+                    }
+                }
+            }
+        }
+
+        Ok(assignments.into_iter().collect())
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
-pub struct CalendarMap(HashMap<String, Calendar>);
+impl TryFrom<(&str, Node<'_>)> for Calendar {
+    type Error = anyhow::Error;
+    fn try_from((year, value): (&str, Node)) -> std::result::Result<Self, Self::Error> {
+        Self::parse(None, year, value, &mut ParseReport::default())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CalendarMap(HashMap<Semester, Calendar>);
 
 impl Deref for CalendarMap {
-    type Target = HashMap<String, Calendar>;
+    type Target = HashMap<Semester, Calendar>;
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
 
-lazy_static! {
-    static ref SEM_YEAR: Regex = Regex::new(r"(\d+) Exam Calendar$").unwrap();
+impl Serialize for CalendarMap {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut state = serializer.serialize_struct("CalendarMap", 2)?;
+        state.serialize_field("schema_version", &SCHEMA_VERSION)?;
+        // Chronological, so regenerated files only differ where the exams do
+        let semesters: BTreeMap<_, _> = self.0.iter().collect();
+        state.serialize_field("semesters", &semesters)?;
+        state.end()
+    }
 }
 
-impl TryFrom<Document> for CalendarMap {
-    type Error = anyhow::Error;
-    fn try_from(value: Document) -> std::result::Result<Self, Self::Error> {
+struct CalendarMapVisitor;
+
+impl<'de> Visitor<'de> for CalendarMapVisitor {
+    type Value = CalendarMap;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a versioned calendar map, or a v1 map of semesters to calendars")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> std::result::Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut inner = HashMap::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "schema_version" => {
+                    let version: u32 = map.next_value()?;
+                    if version > SCHEMA_VERSION {
+                        return Err(serde::de::Error::custom(format!(
+                            "schema_version {version} is newer than the supported {SCHEMA_VERSION}"
+                        )));
+                    }
+                }
+                "semesters" => inner.extend(map.next_value::<HashMap<Semester, Calendar>>()?),
+                // v1 has no wrapper object, semesters are the top level keys
+                semester => {
+                    let semester = semester.parse().map_err(serde::de::Error::custom)?;
+                    inner.insert(semester, map.next_value()?);
+                }
+            }
+        }
+        Ok(CalendarMap(inner))
+    }
+}
+
+impl<'de> Deserialize<'de> for CalendarMap {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(CalendarMapVisitor)
+    }
+}
+
+impl CalendarMap {
+    /// Looks up a semester by name, e.g. "Fall 2023".
+    pub fn semester<S: AsRef<str>>(&self, name: S) -> Option<&Calendar> {
+        self.get(&name.as_ref().parse().ok()?)
+    }
+
+    /// Parses the exam calendar page, returning every skipped table, row, and cell alongside the
+    /// calendars.
+    pub fn parse(value: &Document) -> Result<(Self, ParseReport)> {
         let semesters: Vec<_> = value
             .find(Name("h2"))
             .map(|node| node.text())
             .filter(|node| node.ends_with("Exam Calendar"))
             .collect();
-        let mut semester_years = Vec::with_capacity(semesters.len());
-        for sem in &semesters {
-            let cap = SEM_YEAR.captures(sem).ok_or(anyhow!(
-                "\"{sem}\" does not contained expected pattern \"{}\"",
-                SEM_YEAR.as_str()
-            ))?[1]
-                .to_string();
-            semester_years.push(cap)
-        }
-
-        let mut sem_idx = 0;
-        let cals = value.find(Name("table")).filter_map(|table| {
-            if let Ok(cal) = Calendar::try_from((semester_years[sem_idx].as_str(), table)) {
-                sem_idx += 1;
-                Some(cal)
-            } else {
-                None
-            }
-        });
 
-        let sem_len = semesters.len();
-        let inner = semesters.into_iter().zip(cals).collect();
+        let mut report = ParseReport::default();
+        let mut cals = HashMap::with_capacity(semesters.len());
+        // Each heading is used up by the first exam table after it
+        let mut next = 0;
+        for table in value.find(Name("table")) {
+            let Some(semester) = semesters.get(next) else {
+                report.skip(
+                    SkipKind::Table,
+                    None,
+                    table_head_text(&table),
+                    "No semester heading left for table",
+                );
+                continue;
+            };
+            let key = semester.parse::<Semester>();
+            let year = key
+                .as_ref()
+                .map(|key| key.year.to_string())
+                .unwrap_or_default();
+            let mut table_report = ParseReport::default();
+            match Calendar::parse(Some(semester), &year, table, &mut table_report) {
+                Ok(cal) => {
+                    next += 1;
+                    match key {
+                        Ok(key) => {
+                            report.skipped.extend(table_report.skipped);
+                            cals.insert(key, cal);
+                        }
+                        // Skip the whole table, not every row in it
+                        Err(e) => {
+                            report.skip(SkipKind::Table, Some(semester), table_head_text(&table), e)
+                        }
+                    }
+                }
+                Err(e) => {
+                    report.skipped.extend(table_report.skipped);
+                    report.skip(SkipKind::Table, Some(semester), table_head_text(&table), e)
+                }
+            }
+        }
 
-        if sem_len != sem_idx {
+        if semesters.len() != next {
             bail!(
-                "Number of semesters ({}) != number of calendars ({})",
-                sem_len,
-                sem_idx + 1
+                "Number of semesters ({}) != number of calendars ({next})\n{report}",
+                semesters.len(),
             )
         }
 
-        Ok(Self(inner))
+        Ok((Self(cals), report))
+    }
+
+    /// Parses a saved copy of the exam calendar page.
+    pub fn from_html_str<S: AsRef<str>>(html: S) -> Result<(Self, ParseReport)> {
+        Self::parse(&Document::from(html.as_ref()))
+    }
+
+    /// Parses a saved copy of the exam calendar page from any reader (e.g. a file).
+    pub fn from_reader<R: Read>(reader: R) -> Result<(Self, ParseReport)> {
+        Self::parse(&Document::from_read(reader)?)
+    }
+}
+
+fn table_head_text(table: &Node) -> String {
+    table.find(Name("tr")).next().unwrap_or(*table).text()
+}
+
+impl TryFrom<Document> for CalendarMap {
+    type Error = anyhow::Error;
+    fn try_from(value: Document) -> std::result::Result<Self, Self::Error> {
+        Ok(Self::parse(&value)?.0)
+    }
+}
+
+impl FromIterator<(Semester, Calendar)> for CalendarMap {
+    fn from_iter<T: IntoIterator<Item = (Semester, Calendar)>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
     }
 }
 
 pub async fn get_calendars<S: AsRef<str>>(url: S) -> Result<CalendarMap> {
-    let text = get_page_document(url).await?;
-    CalendarMap::try_from(text)
+    Ok(get_calendars_with_report(url).await?.0)
+}
+
+pub async fn get_calendars_with_report<S: AsRef<str>>(
+    url: S,
+) -> Result<(CalendarMap, ParseReport)> {
+    get_calendars_with(&ReqwestFetcher::new()?, url).await
+}
+
+/// Like [`get_calendars_with_report`], getting the page from `fetcher`.
+pub async fn get_calendars_with<F: PageFetcher, S: AsRef<str>>(
+    fetcher: &F,
+    url: S,
+) -> Result<(CalendarMap, ParseReport)> {
+    let text = get_page_document_with(fetcher, url).await?;
+    CalendarMap::parse(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<html><body>
+<h2>Fall 2023 Exam Calendar</h2>
+<table><thead><tr><th>Exam Dates/Times</th><th>8:30 a.m. – 11:00 a.m.</th><th>12:00 p.m. – 2:30 p.m.</th></tr></thead>
+<tbody>
+<tr><td>Friday, Dec. 8</td><td>10:15 a.m. MW<br>10:15 a.m. WF</td><td>Common:<br>PY 211</td></tr>
+<tr><td>Bogus, Date</td><td>8:30 a.m. M</td><td></td></tr>
+<tr><td>Monday, Dec. 11</td><td>10:15 a.m. MWF</td><td>garbage 99:99 - zz</td><td>1:30 p.m. MW</td></tr>
+</tbody></table>
+<h2>Spring 2024 Exam Calendar</h2>
+<table><thead><tr><th>Something else</th></tr></thead><tbody><tr><td>x</td></tr></tbody></table>
+<table><thead><tr><th>Exam Dates/Times</th><th>8:30 a.m. – 11:00 a.m.</th></tr></thead>
+<tbody><tr><td>Monday, April 29</td><td>7:30 a.m. – 8:55 a.m.</td></tr></tbody></table>
+</body></html>"#;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn report_lists_every_skipped_table_row_and_cell() {
+        let (cals, report) = CalendarMap::from_html_str(PAGE).unwrap();
+        assert_eq!(cals.len(), 2);
+
+        let skipped: Vec<_> = report
+            .skipped
+            .iter()
+            .map(|skipped| {
+                (
+                    skipped.kind,
+                    skipped.semester.as_deref(),
+                    skipped.text.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            skipped,
+            [
+                (
+                    SkipKind::Row,
+                    Some("Fall 2023 Exam Calendar"),
+                    "Bogus, Date8:30 a.m. M"
+                ),
+                (
+                    SkipKind::Cell,
+                    Some("Fall 2023 Exam Calendar"),
+                    "garbage 99:99 - zz"
+                ),
+                (
+                    SkipKind::Cell,
+                    Some("Fall 2023 Exam Calendar"),
+                    "1:30 p.m. MW"
+                ),
+                (
+                    SkipKind::Table,
+                    Some("Spring 2024 Exam Calendar"),
+                    "Something else"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn report_is_empty_for_a_clean_page() {
+        let page = PAGE
+            .replace("<tr><td>Bogus, Date</td><td>8:30 a.m. M</td><td></td></tr>", "")
+            .replace("<td>garbage 99:99 - zz</td><td>1:30 p.m. MW</td>", "")
+            .replace(
+                "<table><thead><tr><th>Something else</th></tr></thead><tbody><tr><td>x</td></tr></tbody></table>",
+                "",
+            );
+        let (cals, report) = CalendarMap::from_html_str(page).unwrap();
+        assert!(report.is_empty(), "{report}");
+        assert_eq!(
+            cals.semester("Spring 2024")
+                .unwrap()
+                .exams(&Class::Range(time(7, 30)..time(8, 55))),
+            [Exam::new(date(2024, 4, 29), time(8, 30)..time(11, 0))]
+        );
+    }
+
+    #[test]
+    fn superset_ties_are_all_reported() {
+        use Weekday::*;
+        let page = PAGE
+            .replace("10:15 a.m. MW<br>10:15 a.m. WF", "10:15 a.m. MW")
+            .replace("10:15 a.m. MWF", "10:15 a.m. WF");
+        let (cals, _) = CalendarMap::from_html_str(page).unwrap();
+        let fall = cals.semester("Fall 2023").unwrap();
+
+        let found = fall
+            .exam_for_meeting(&[Monday, Wednesday, Friday], time(10, 15))
+            .unwrap();
+        assert_eq!(found.kind, MatchKind::DaysSuperset);
+        assert_eq!(
+            found.class,
+            &Class::Time(vec![Monday, Wednesday], time(10, 15))
+        );
+        assert_eq!(found.exams[0].date, date(2023, 12, 8));
+        assert!(found.is_ambiguous());
+        assert_eq!(
+            found.others,
+            [(
+                &Class::Time(vec![Wednesday, Friday], time(10, 15)),
+                [Exam::new(date(2023, 12, 11), time(8, 30)..time(11, 0))].as_slice()
+            )]
+        );
+        assert!(found.ambiguity().is_some());
+
+        let (cals, _) = CalendarMap::from_html_str(PAGE).unwrap();
+        let found = cals
+            .semester("Fall 2023")
+            .unwrap()
+            .exam_for_meeting(&[Monday, Wednesday, Friday], time(10, 15))
+            .unwrap();
+        assert_eq!(found.kind, MatchKind::Exact);
+        assert!(!found.is_ambiguous());
+        assert_eq!(found.ambiguity(), None);
+    }
+
+    #[test]
+    fn exam_ending_at_midnight_ends_the_next_day() {
+        let exam = Exam::new(date(2023, 12, 8), time(21, 0)..time(0, 0));
+        assert_eq!(exam.end().date_naive(), date(2023, 12, 9));
+        assert_eq!(exam.end() - exam.start(), Duration::hours(3));
+
+        let exam = Exam::new(date(2023, 12, 8), time(19, 0)..time(21, 30));
+        assert_eq!(exam.end().date_naive(), date(2023, 12, 8));
+    }
+
+    #[test]
+    fn parse_time_accepts_page_and_typed_forms() {
+        for (text, expected) in [
+            ("8:30 a.m.", time(8, 30)),
+            ("8:30am", time(8, 30)),
+            ("8:30 AM", time(8, 30)),
+            ("3:30 p.m.", time(15, 30)),
+            ("noon", time(12, 0)),
+            ("Noon", time(12, 0)),
+            ("12 noon", time(12, 0)),
+            ("midnight", time(0, 0)),
+            ("12 p.m.", time(12, 0)),
+            ("12 a.m.", time(0, 0)),
+            ("13:05:00", time(13, 5)),
+            ("8:30\u{a0}a.m.", time(8, 30)),
+            ("8:30\u{202f}a.m.", time(8, 30)),
+        ] {
+            assert_eq!(parse_time(text).unwrap(), expected, "{text}");
+        }
+    }
+
+    #[test]
+    fn parse_time_rejects_bad_input() {
+        for text in ["", "zz", "99:99", "13 p.m.", "0 a.m.", "8:30 x.m.", "25:00"] {
+            assert!(parse_time(text).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn class_ranges_split_on_any_dash() {
+        for text in [
+            "7:30 a.m. - 8:55 a.m.",
+            "7:30 a.m. – 8:55 a.m.",
+            "7:30 a.m. — 8:55 a.m.",
+            "7:30\u{a0}a.m.\u{a0}–\u{a0}8:55\u{a0}a.m.",
+        ] {
+            assert_eq!(
+                Class::factory(text).unwrap(),
+                [Class::Range(time(7, 30)..time(8, 55))],
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn range_start_takes_the_ends_meridiem() {
+        for (start, end, expected) in [
+            ("1", "3:30 pm", time(13, 0)..time(15, 30)),
+            ("11", "1 pm", time(11, 0)..time(13, 0)),
+            ("8:30", "11 am", time(8, 30)..time(11, 0)),
+            ("9 pm", "midnight", time(21, 0)..time(0, 0)),
+            ("12 noon", "2:30 pm", time(12, 0)..time(14, 30)),
+            ("13:00", "15:30", time(13, 0)..time(15, 30)),
+        ] {
+            assert_eq!(
+                parse_time_range(start, end).unwrap(),
+                expected,
+                "{start} - {end}"
+            );
+        }
+    }
+
+    #[test]
+    fn header_ranges_read_as_written() {
+        let page = PAGE
+            .replace("8:30 a.m. – 11:00 a.m.", "1 – 3:30 p.m.")
+            .replace("12:00 p.m. – 2:30 p.m.", "9 p.m. – midnight");
+        let (cals, _) = CalendarMap::from_html_str(page).unwrap();
+        let fall = cals.semester("Fall 2023").unwrap();
+        assert_eq!(
+            fall.exams(&Class::Name("PY 211".to_string())),
+            [Exam::new(date(2023, 12, 8), time(21, 0)..time(0, 0))]
+        );
+        let found = fall
+            .exam_for_meeting(&[Weekday::Monday, Weekday::Wednesday], time(10, 15))
+            .unwrap();
+        assert_eq!(
+            found.exams,
+            [Exam::new(date(2023, 12, 8), time(13, 0)..time(15, 30))]
+        );
+
+        let late = Calendar::from_iter([(
+            Class::Range(time(21, 0)..time(0, 0)),
+            Exam::new(date(2023, 12, 8), time(19, 0)..time(21, 30)),
+        )]);
+        assert!(late
+            .exam_for_meeting(&[Weekday::Monday], time(22, 0))
+            .is_some());
+        assert!(late
+            .exam_for_meeting(&[Weekday::Monday], time(20, 0))
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn fs_fetcher_reads_the_page_by_url_path() {
+        let root =
+            std::env::temp_dir().join(format!("ncsu_exam_fs_fetcher_{}", std::process::id()));
+        let dir = root.join("calendars").join("exam-calendar");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("index.html"), PAGE).unwrap();
+        let fetcher = crate::fetch::FsFetcher::new(&root);
+
+        let fetched =
+            get_calendars_with(&fetcher, "https://example.edu/calendars/exam-calendar/").await;
+        let missing = get_calendars_with(&fetcher, "https://example.edu/calendars/other/").await;
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let (cals, report) = fetched.unwrap();
+        assert_eq!((cals, report), CalendarMap::from_html_str(PAGE).unwrap());
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn unknown_term_skips_only_its_table() {
+        let page = PAGE.replace("Spring 2024 Exam Calendar", "Winter 2024 Exam Calendar");
+        let (cals, report) = CalendarMap::from_html_str(page).unwrap();
+        assert_eq!(
+            cals.keys().collect::<Vec<_>>(),
+            [&"Fall 2023".parse().unwrap()]
+        );
+        let skipped = report.skipped.last().unwrap();
+        assert_eq!(skipped.kind, SkipKind::Table);
+        assert_eq!(
+            skipped.semester.as_deref(),
+            Some("Winter 2024 Exam Calendar")
+        );
+        assert!(skipped.reason.contains("Winter"), "{skipped}");
+    }
+
+    #[test]
+    fn class_display_reads_back() {
+        use Weekday::*;
+        for (text, class) in [
+            (
+                "MWF 10:15 AM",
+                Class::Time(vec![Monday, Wednesday, Friday], time(10, 15)),
+            ),
+            (
+                "TuTh 1:30 PM",
+                Class::Time(vec![Tuesday, Thursday], time(13, 30)),
+            ),
+            ("7:30 AM – 8:55 AM", Class::Range(time(7, 30)..time(8, 55))),
+            ("MA 141", Class::Name("MA 141".to_string())),
+        ] {
+            assert_eq!(text.parse::<Class>().unwrap(), class, "{text}");
+            assert_eq!(class.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn class_reads_typed_forms() {
+        use Weekday::*;
+        for (text, class) in [
+            (
+                "MWF 10:15am",
+                Class::Time(vec![Monday, Wednesday, Friday], time(10, 15)),
+            ),
+            (
+                "10:15 a.m. MWF",
+                Class::Time(vec![Monday, Wednesday, Friday], time(10, 15)),
+            ),
+            ("R 1:30 PM", Class::Time(vec![Thursday], time(13, 30))),
+            (
+                "TTh 1:30",
+                Class::Time(vec![Tuesday, Thursday], time(13, 30)),
+            ),
+            (
+                "TR 1:30 pm",
+                Class::Time(vec![Tuesday, Thursday], time(13, 30)),
+            ),
+            ("7:30-8:55", Class::Range(time(7, 30)..time(8, 55))),
+            ("FR 101", Class::Name("FR 101".to_string())),
+            ("CSC 316", Class::Name("CSC 316".to_string())),
+        ] {
+            assert_eq!(text.parse::<Class>().unwrap(), class, "{text}");
+        }
+        assert!("10:15".parse::<Class>().is_err());
+        assert!("".parse::<Class>().is_err());
+    }
+
+    #[test]
+    fn parse_days_takes_the_longest_token() {
+        use Weekday::*;
+        assert_eq!(Weekday::parse_days("R").unwrap(), [Thursday]);
+        assert_eq!(Weekday::parse_days("TTh").unwrap(), [Tuesday, Thursday]);
+        assert_eq!(Weekday::parse_days("Mon/Wed").unwrap(), [Monday, Wednesday]);
+        assert_eq!(
+            Weekday::parse_days("friday, monday").unwrap(),
+            [Monday, Friday]
+        );
+        for day in [Monday, Tuesday, Wednesday, Thursday, Friday] {
+            assert_eq!(Weekday::from_name(day.name()).unwrap(), day);
+            assert_eq!(day.to_string().parse::<Weekday>().unwrap(), day);
+        }
+        assert!(Weekday::parse_days("MA").is_err());
+        assert!(Weekday::parse_days("").is_err());
+    }
+
+    const V1_JSON: &str = r#"{"Fall 2023 Exam Calendar": {
+        "[Monday, Wednesday] 11:45:00": ["2023-12-13", {"start": "12:00:00", "end": "14:30:00"}],
+        "07:30:00..08:55:00": ["2023-12-08", {"start": "08:30:00", "end": "11:00:00"}],
+        "PY 211": ["2023-12-07", {"start": "19:00:00", "end": "21:30:00"}]
+    }}"#;
+
+    #[test]
+    fn v1_json_migrates_to_v2() {
+        let cals: CalendarMap = serde_json::from_str(V1_JSON).unwrap();
+        let fall = cals.semester("Fall 2023").unwrap();
+        assert_eq!(
+            fall.exams(&Class::Time(
+                vec![Weekday::Monday, Weekday::Wednesday],
+                time(11, 45)
+            )),
+            [Exam::new(date(2023, 12, 13), time(12, 0)..time(14, 30))]
+        );
+        assert_eq!(
+            fall.exams(&Class::Range(time(7, 30)..time(8, 55))),
+            [Exam::new(date(2023, 12, 8), time(8, 30)..time(11, 0))]
+        );
+        assert_eq!(
+            fall.exams(&Class::Name("PY 211".to_string())),
+            [Exam::new(date(2023, 12, 7), time(19, 0)..time(21, 30))]
+        );
+
+        let v2 = serde_json::to_value(&cals).unwrap();
+        assert_eq!(v2["schema_version"], SCHEMA_VERSION);
+        assert_eq!(
+            v2["semesters"]["Fall 2023"][0]["class"],
+            serde_json::json!({"kind": "time", "days": ["Monday", "Wednesday"], "time": "11:45:00"})
+        );
+        assert_eq!(serde_json::from_value::<CalendarMap>(v2).unwrap(), cals);
+    }
+
+    #[test]
+    fn v2_json_round_trips() {
+        let (cals, _) = CalendarMap::from_html_str(PAGE).unwrap();
+        let json = serde_json::to_string(&cals).unwrap();
+        let read: CalendarMap = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, cals);
+        assert_eq!(serde_json::to_string(&read).unwrap(), json);
+    }
+
+    #[test]
+    fn newer_schema_is_rejected() {
+        let json = format!(
+            r#"{{"schema_version": {}, "semesters": {{}}}}"#,
+            SCHEMA_VERSION + 1
+        );
+        assert!(serde_json::from_str::<CalendarMap>(&json).is_err());
+    }
 }
@@ -0,0 +1,322 @@
+/*
+* Copyright (C) 2023 Bennett Petzold
+*
+* This file is part of ncsu_exam_calendar.
+*
+* ncsu_exam_calendar is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 2 of the License, or (at your option) any later version.
+*
+* ncsu_exam_calendar is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License along with ncsu_exam_calendar. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Changes between two copies of the exam calendar.
+
+use std::{collections::BTreeSet, fmt::Display};
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::{
+    calendar::{Calendar, CalendarMap, Class, Exam},
+    semester::Semester,
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CalendarDiff {
+    pub added_semesters: Vec<Semester>,
+    pub removed_semesters: Vec<Semester>,
+    /// Semesters in both copies whose exams changed.
+    pub changed_semesters: Vec<SemesterDiff>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SemesterDiff {
+    pub semester: Semester,
+    pub added: Vec<ClassExams>,
+    pub removed: Vec<ClassExams>,
+    pub moved: Vec<MovedExams>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClassExams {
+    pub class: Class,
+    pub exams: Vec<Exam>,
+}
+
+/// A class listed in both copies, with different exams.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MovedExams {
+    pub class: Class,
+    pub old: Vec<Exam>,
+    pub new: Vec<Exam>,
+}
+
+impl CalendarDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_semesters.is_empty()
+            && self.removed_semesters.is_empty()
+            && self.changed_semesters.is_empty()
+    }
+
+    /// Whether any class kept in both copies has a different exam.
+    pub fn has_moved(&self) -> bool {
+        self.changed_semesters
+            .iter()
+            .any(|semester| !semester.moved.is_empty())
+    }
+}
+
+impl CalendarMap {
+    /// What changed going from `self` to `new`.
+    pub fn diff(&self, new: &CalendarMap) -> CalendarDiff {
+        let mut diff = CalendarDiff::default();
+        for semester in self.keys().chain(new.keys()).collect::<BTreeSet<_>>() {
+            match (self.get(semester), new.get(semester)) {
+                (Some(_), None) => diff.removed_semesters.push(*semester),
+                (None, Some(_)) => diff.added_semesters.push(*semester),
+                (Some(old), Some(new)) => {
+                    if let Some(changed) = SemesterDiff::new(*semester, old, new) {
+                        diff.changed_semesters.push(changed);
+                    }
+                }
+                (None, None) => (),
+            }
+        }
+        diff
+    }
+}
+
+impl SemesterDiff {
+    fn new(semester: Semester, old: &Calendar, new: &Calendar) -> Option<Self> {
+        let exams = |calendar: &Calendar, class: &Class| -> Vec<Exam> {
+            calendar.exams(class).iter().cloned().sorted().collect()
+        };
+        let mut diff = Self {
+            semester,
+            added: vec![],
+            removed: vec![],
+            moved: vec![],
+        };
+
+        for class in old.keys().chain(new.keys()).collect::<BTreeSet<_>>() {
+            let class = class.clone();
+            match (old.contains_key(&class), new.contains_key(&class)) {
+                (true, false) => diff.removed.push(ClassExams {
+                    exams: exams(old, &class),
+                    class,
+                }),
+                (false, true) => diff.added.push(ClassExams {
+                    exams: exams(new, &class),
+                    class,
+                }),
+                _ => {
+                    let (old, new) = (exams(old, &class), exams(new, &class));
+                    if old != new {
+                        diff.moved.push(MovedExams { class, old, new });
+                    }
+                }
+            }
+        }
+
+        (!diff.added.is_empty() || !diff.removed.is_empty() || !diff.moved.is_empty())
+            .then_some(diff)
+    }
+}
+
+fn exam_list(exams: &[Exam]) -> String {
+    if exams.is_empty() {
+        "no exam".to_string()
+    } else {
+        exams.iter().join("; ")
+    }
+}
+
+impl Display for CalendarDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for semester in &self.added_semesters {
+            writeln!(f, "Added {semester}")?;
+        }
+        for semester in &self.removed_semesters {
+            writeln!(f, "Removed {semester}")?;
+        }
+        for semester in &self.changed_semesters {
+            write!(f, "{semester}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for SemesterDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Changed {}:", self.semester)?;
+        for added in &self.added {
+            writeln!(f, "  + {}: {}", added.class, exam_list(&added.exams))?;
+        }
+        for removed in &self.removed {
+            writeln!(f, "  - {}: {}", removed.class, exam_list(&removed.exams))?;
+        }
+        for moved in &self.moved {
+            writeln!(
+                f,
+                "  ~ {}: {} -> {}",
+                moved.class,
+                exam_list(&moved.old),
+                exam_list(&moved.new)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveTime};
+    use serde_json::json;
+
+    use super::*;
+
+    fn exam(day: u32, hour: u32) -> Exam {
+        let time = |hour| NaiveTime::from_hms_opt(hour, 0, 0).unwrap();
+        Exam::new(
+            NaiveDate::from_ymd_opt(2023, 12, day).unwrap(),
+            time(hour)..time(hour + 2),
+        )
+    }
+
+    fn class(name: &str) -> Class {
+        Class::Name(name.to_string())
+    }
+
+    fn map(semesters: &[(&str, &[(&str, Exam)])]) -> CalendarMap {
+        semesters
+            .iter()
+            .map(|(semester, entries)| {
+                (
+                    semester.parse().unwrap(),
+                    entries
+                        .iter()
+                        .map(|(name, exam)| (class(name), exam.clone()))
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical_calendars_have_no_diff() {
+        let old = map(&[("Fall 2023", &[("PY 211", exam(8, 19))])]);
+        let diff = old.diff(&old.clone());
+        assert!(diff.is_empty());
+        assert!(!diff.has_moved());
+        assert_eq!(diff.to_string(), "");
+    }
+
+    #[test]
+    fn added_and_removed_semesters() {
+        let old = map(&[("Fall 2023", &[("PY 211", exam(8, 19))])]);
+        let new = map(&[("Spring 2024", &[("PY 211", exam(8, 19))])]);
+        let diff = old.diff(&new);
+        assert_eq!(diff.added_semesters, ["Spring 2024".parse().unwrap()]);
+        assert_eq!(diff.removed_semesters, ["Fall 2023".parse().unwrap()]);
+        assert!(diff.changed_semesters.is_empty());
+        assert!(!diff.has_moved());
+        assert_eq!(diff.to_string(), "Added Spring 2024\nRemoved Fall 2023\n");
+    }
+
+    #[test]
+    fn added_and_removed_classes_are_not_moves() {
+        let old = map(&[(
+            "Fall 2023",
+            &[("PY 211", exam(8, 19)), ("MA 141", exam(11, 8))],
+        )]);
+        let new = map(&[(
+            "Fall 2023",
+            &[("PY 211", exam(8, 19)), ("CH 101", exam(12, 8))],
+        )]);
+        let diff = old.diff(&new);
+        let [semester] = diff.changed_semesters.as_slice() else {
+            panic!("{diff:?}")
+        };
+        assert_eq!(
+            semester.added,
+            [ClassExams {
+                class: class("CH 101"),
+                exams: vec![exam(12, 8)]
+            }]
+        );
+        assert_eq!(
+            semester.removed,
+            [ClassExams {
+                class: class("MA 141"),
+                exams: vec![exam(11, 8)]
+            }]
+        );
+        assert!(semester.moved.is_empty());
+        assert!(!diff.is_empty());
+        assert!(!diff.has_moved());
+    }
+
+    #[test]
+    fn moved_exams() {
+        let old = map(&[("Fall 2023", &[("PY 211", exam(8, 19))])]);
+        let new = map(&[("Fall 2023", &[("PY 211", exam(9, 8))])]);
+        let diff = old.diff(&new);
+        assert!(diff.has_moved());
+        assert_eq!(
+            diff.changed_semesters[0].moved,
+            [MovedExams {
+                class: class("PY 211"),
+                old: vec![exam(8, 19)],
+                new: vec![exam(9, 8)]
+            }]
+        );
+        assert_eq!(
+            diff.to_string(),
+            "Changed Fall 2023:\n  ~ PY 211: Friday, December  8, 7:00 PM - 9:00 PM -> Saturday, December  9, 8:00 AM - 10:00 AM\n"
+        );
+    }
+
+    #[test]
+    fn second_listing_is_a_move() {
+        let old = map(&[("Fall 2023", &[("PY 211", exam(8, 19))])]);
+        let new = map(&[(
+            "Fall 2023",
+            &[("PY 211", exam(11, 8)), ("PY 211", exam(8, 19))],
+        )]);
+        let diff = old.diff(&new);
+        assert!(diff.has_moved());
+        // Exams are compared in date order, not page order
+        assert_eq!(
+            diff.changed_semesters[0].moved[0].new,
+            [exam(8, 19), exam(11, 8)]
+        );
+    }
+
+    #[test]
+    fn json_shape() {
+        let old = map(&[("Fall 2023", &[("PY 211", exam(8, 19))])]);
+        let new = map(&[
+            ("Fall 2023", &[("PY 211", exam(9, 8))]),
+            ("Spring 2024", &[]),
+        ]);
+        let exam_json = |date: &str, start: &str, end: &str| json!({"date": date, "time": {"start": start, "end": end}});
+        assert_eq!(
+            serde_json::to_value(old.diff(&new)).unwrap(),
+            json!({
+                "added_semesters": ["Spring 2024"],
+                "removed_semesters": [],
+                "changed_semesters": [{
+                    "semester": "Fall 2023",
+                    "added": [],
+                    "removed": [],
+                    "moved": [{
+                        "class": {"kind": "name", "name": "PY 211"},
+                        "old": [exam_json("2023-12-08", "19:00:00", "21:00:00")],
+                        "new": [exam_json("2023-12-09", "08:00:00", "10:00:00")],
+                    }],
+                }],
+            })
+        );
+    }
+}
@@ -0,0 +1,299 @@
+/*
+* Copyright (C) 2023 Bennett Petzold
+*
+* This file is part of ncsu_exam_calendar.
+*
+* ncsu_exam_calendar is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 2 of the License, or (at your option) any later version.
+*
+* ncsu_exam_calendar is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License along with ncsu_exam_calendar. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Getting the exam page, over HTTP or from disk.
+
+use std::{future::Future, path::PathBuf, time::Duration};
+
+use anyhow::{Context, Result};
+use reqwest::{
+    header::{HeaderName, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    Client, RequestBuilder, Response, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_USER_AGENT: &str = concat!("ncsu_exam_calendar/", env!("CARGO_PKG_VERSION"));
+
+/// What the server sent to recognize this version of a page later.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fetched {
+    /// The page still matches the given [`Validators`].
+    NotModified,
+    Page {
+        body: String,
+        validators: Validators,
+    },
+}
+
+/// Source of page bodies for the calendar parser.
+pub trait PageFetcher {
+    /// The body of the page at `url`.
+    fn fetch(&self, url: &str) -> impl Future<Output = Result<String>>;
+
+    /// Whether `url` looks fetchable, without downloading it.
+    fn probe(&self, url: &str) -> impl Future<Output = bool>;
+
+    /// The page at `url`, unless it still matches `known`.
+    ///
+    /// Fetchers without conditional requests always return the page.
+    fn fetch_if_changed(
+        &self,
+        url: &str,
+        known: Option<&Validators>,
+    ) -> impl Future<Output = Result<Fetched>> {
+        let _ = known;
+        async move {
+            Ok(Fetched::Page {
+                body: self.fetch(url).await?,
+                validators: Validators::default(),
+            })
+        }
+    }
+}
+
+/// Fetches over HTTP, retrying network errors and server errors.
+#[derive(Debug, Clone)]
+pub struct ReqwestFetcher {
+    client: Client,
+    retries: u32,
+    #[cfg_attr(target_family = "wasm", allow(dead_code))]
+    backoff: Duration,
+}
+
+impl ReqwestFetcher {
+    pub fn new() -> Result<Self> {
+        Self::builder().build()
+    }
+
+    pub fn builder() -> ReqwestFetcherBuilder {
+        ReqwestFetcherBuilder::default()
+    }
+
+    fn should_retry(result: &reqwest::Result<Response>) -> bool {
+        match result {
+            Ok(response) => {
+                response.status().is_server_error()
+                    || response.status() == StatusCode::TOO_MANY_REQUESTS
+            }
+            Err(e) => !e.is_builder(),
+        }
+    }
+
+    /// POSTs `body` as JSON with the same settings and retries as fetching.
+    pub async fn post_json(&self, url: &str, body: String) -> Result<()> {
+        self.send(|| {
+            self.client
+                .post(url)
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.clone())
+        })
+        .await?
+        .error_for_status()?;
+        Ok(())
+    }
+
+    async fn send<F: Fn() -> RequestBuilder>(&self, request: F) -> Result<Response> {
+        let mut attempt = 0;
+        loop {
+            let result = request().send().await;
+            if attempt >= self.retries || !Self::should_retry(&result) {
+                return Ok(result?);
+            }
+            // Browsers have no timer to sleep on here, so retry right away
+            #[cfg(not(target_family = "wasm"))]
+            tokio::time::sleep(self.backoff * 2_u32.saturating_pow(attempt)).await;
+            attempt += 1;
+        }
+    }
+}
+
+impl PageFetcher for ReqwestFetcher {
+    async fn fetch(&self, url: &str) -> Result<String> {
+        let response = self.send(|| self.client.get(url)).await?;
+        Ok(response.error_for_status()?.text().await?)
+    }
+
+    async fn fetch_if_changed(&self, url: &str, known: Option<&Validators>) -> Result<Fetched> {
+        let response = self
+            .send(|| {
+                let mut request = self.client.get(url);
+                if let Some(etag) = known.and_then(|known| known.etag.as_ref()) {
+                    request = request.header(IF_NONE_MATCH, etag);
+                }
+                if let Some(date) = known.and_then(|known| known.last_modified.as_ref()) {
+                    request = request.header(IF_MODIFIED_SINCE, date);
+                }
+                request
+            })
+            .await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(Fetched::NotModified);
+        }
+
+        let response = response.error_for_status()?;
+        let header = |name: HeaderName| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let validators = Validators {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        };
+        Ok(Fetched::Page {
+            body: response.text().await?,
+            validators,
+        })
+    }
+
+    async fn probe(&self, url: &str) -> bool {
+        match self.client.head(url).send().await {
+            Ok(response) => response.error_for_status().is_ok(),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Settings for a [`ReqwestFetcher`].
+///
+/// Timeouts and proxies are left to the browser on the web.
+#[derive(Debug, Clone)]
+pub struct ReqwestFetcherBuilder {
+    timeout: Option<Duration>,
+    retries: u32,
+    backoff: Duration,
+    user_agent: String,
+    proxy: Option<String>,
+}
+
+impl Default for ReqwestFetcherBuilder {
+    fn default() -> Self {
+        Self {
+            timeout: Some(Duration::from_secs(30)),
+            retries: 2,
+            backoff: Duration::from_millis(500),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            proxy: None,
+        }
+    }
+}
+
+impl ReqwestFetcherBuilder {
+    /// Limit on each whole request, or `None` to wait forever.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Extra attempts after the first one fails.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Wait before the first retry, doubled for each one after.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn user_agent<S: Into<String>>(mut self, user_agent: S) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Send every request through this proxy URL.
+    pub fn proxy<S: Into<String>>(mut self, proxy: Option<S>) -> Self {
+        self.proxy = proxy.map(Into::into);
+        self
+    }
+
+    pub fn build(self) -> Result<ReqwestFetcher> {
+        let builder = Client::builder().user_agent(self.user_agent);
+
+        #[cfg(not(target_family = "wasm"))]
+        let builder = {
+            let mut builder = builder;
+            if let Some(timeout) = self.timeout {
+                builder = builder.timeout(timeout);
+            }
+            if let Some(proxy) = self.proxy {
+                builder = builder.proxy(
+                    reqwest::Proxy::all(&proxy)
+                        .with_context(|| format!("Bad proxy \"{proxy}\""))?,
+                );
+            }
+            builder
+        };
+
+        Ok(ReqwestFetcher {
+            client: builder.build()?,
+            retries: self.retries,
+            backoff: self.backoff,
+        })
+    }
+}
+
+/// Serves pages from a directory, laid out by URL path.
+///
+/// `https://host/calendars/exam-calendar/` is read from
+/// `{root}/calendars/exam-calendar/index.html`.
+#[derive(Debug, Clone)]
+pub struct FsFetcher {
+    root: PathBuf,
+}
+
+impl FsFetcher {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn path(&self, url: &str) -> Result<PathBuf> {
+        let url = reqwest::Url::parse(url)?;
+        let mut path = self.root.clone();
+        // Parsing already resolved any ".." segments
+        for segment in url.path_segments().into_iter().flatten() {
+            if !segment.is_empty() {
+                path.push(segment);
+            }
+        }
+        if url.path().ends_with('/') {
+            path.push("index.html");
+        }
+        Ok(path)
+    }
+}
+
+impl PageFetcher for FsFetcher {
+    async fn fetch(&self, url: &str) -> Result<String> {
+        let path = self.path(url)?;
+        std::fs::read_to_string(&path).with_context(|| format!("Could not read {}", path.display()))
+    }
+
+    async fn probe(&self, url: &str) -> bool {
+        self.path(url).is_ok_and(|path| path.is_file())
+    }
+}
@@ -16,16 +16,22 @@ use std::ops::Range;
 use std::ops::{Deref, DerefMut};
 
 use anyhow::Result;
-use chrono::NaiveTime;
+use chrono::{NaiveTime, Utc};
 use dioxus::prelude::*;
 use itertools::Itertools;
 
-use reqwest::Client;
-use tokio::runtime::Builder;
-
+use crate::cache::CacheMeta;
+#[cfg(not(target_family = "wasm"))]
+use crate::cache::PageCache;
 use crate::calendar::Class;
+use crate::calendar::MatchKind;
 use crate::calendar::Weekday;
-use crate::calendar::{get_calendars, Calendar, CalendarMap};
+use crate::calendar::{Calendar, CalendarMap, Exam, TIME_FORMAT};
+use crate::fetch::{PageFetcher, ReqwestFetcher};
+use crate::ics::{classes_from_ics, classes_to_ics};
+use crate::rules::{Rule, RuleReport};
+use crate::schedule::{add_class, class_label, Schedule, ScheduledExam};
+use crate::semester::Semester;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SourceType {
@@ -58,38 +64,136 @@ impl From<SourceType> for SourceTypeProp {
     }
 }
 
+/// Progress of loading the default exam data at startup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum LoadState {
+    Loading,
+    Loaded,
+    Failed(String),
+}
+
+/// The personal exam schedule, with course names for imported classes.
+type MyClasses = Vec<(Option<String>, Class)>;
+
 const DEFAULT_URL: &str = "https://studentservices.ncsu.edu/calendars/exam-calendar/";
 const DEFAULT_JSON: &str = "./exams.json";
+#[cfg(not(target_family = "wasm"))]
+const PAGE_CACHE: &str = "./page_cache";
 
 async fn load_default_map() -> Result<CalendarMap> {
     #[cfg(not(target_family = "wasm"))]
     let src = std::fs::read_to_string(DEFAULT_JSON)?;
-    // TODO: Look into fetching this from server instead
     #[cfg(target_family = "wasm")]
-    let src = { include_str!("../../../public/exams.json") };
+    let src = {
+        let (url, requested) = default_json_url()?;
+        match ReqwestFetcher::new()?.fetch(url.as_str()).await {
+            Ok(src) => src,
+            // Showing other data than was asked for would be misleading
+            Err(e) if requested => return Err(e.context(format!("Could not load {url}"))),
+            Err(e) => {
+                log::warn!("Using the built-in exam data, could not fetch: {e}");
+                include_str!("../../../public/exams.json").to_string()
+            }
+        }
+    };
 
     Ok(serde_json::from_str(&src)?)
 }
 
+/// The `?data=` URL if given, otherwise [`DEFAULT_JSON`] beside the page, and whether it was
+/// given.
+#[cfg(target_family = "wasm")]
+fn default_json_url() -> Result<(reqwest::Url, bool)> {
+    let page = web_sys::window()
+        .ok_or_else(|| anyhow::anyhow!("No browser window"))?
+        .location()
+        .href()
+        .map_err(|e| anyhow::anyhow!("No page location: {e:?}"))?;
+    let page = reqwest::Url::parse(&page)?;
+    let data = page
+        .query_pairs()
+        .find(|(key, _)| key == "data")
+        .map(|(_, value)| value.into_owned());
+    let url = match &data {
+        Some(data) => page
+            .join(data)
+            .map_err(|e| anyhow::anyhow!("Bad ?data= URL \"{data}\": {e}"))?,
+        None => page.join(DEFAULT_JSON)?,
+    };
+    Ok((url, data.is_some()))
+}
+
+/// Downloads the calendars at `url`, through [`PAGE_CACHE`] outside the browser.
+async fn load_url(url: &str) -> Result<(CalendarMap, Option<CacheMeta>)> {
+    let fetcher = ReqwestFetcher::new()?;
+    #[cfg(not(target_family = "wasm"))]
+    {
+        let cached = PageCache::new(PAGE_CACHE)
+            .get_calendars(&fetcher, url)
+            .await?;
+        Ok((cached.calendars, Some(cached.meta)))
+    }
+    #[cfg(target_family = "wasm")]
+    Ok((
+        crate::calendar::get_calendars_with(&fetcher, url).await?.0,
+        None,
+    ))
+}
+
 pub fn app(cx: Scope) -> Element {
     let version = "v".to_string() + option_env!("CARGO_PKG_VERSION").unwrap_or("UNKNOWN");
 
-    use_shared_state_provider(cx, || {
-        Builder::new_current_thread()
-            .build()
-            .unwrap()
-            .handle()
-            .block_on(async { load_default_map().await.ok() })
-    });
+    use_shared_state_provider(cx, Option::<CalendarMap>::default);
     let source = use_shared_state::<Option<CalendarMap>>(cx).unwrap();
 
+    let load_state = use_state(cx, || LoadState::Loading);
+    use_future(cx, (), |_| {
+        to_owned![source, load_state];
+        async move {
+            match load_default_map().await {
+                Ok(map) => {
+                    // Keep anything the user loaded while this was running
+                    if source.read().is_none() {
+                        *source.write() = Some(map);
+                    }
+                    load_state.set(LoadState::Loaded);
+                }
+                Err(e) => load_state.set(LoadState::Failed(format!("{e:#}"))),
+            }
+        }
+    });
+
     use_shared_state_provider(cx, Option::<Calendar>::default);
     let semester = use_shared_state::<Option<Calendar>>(cx).unwrap();
 
+    use_shared_state_provider(cx, Option::<Semester>::default);
+    use_shared_state_provider(cx, MyClasses::new);
+
+    use_shared_state_provider(cx, Option::<CacheMeta>::default);
+    let page_meta = use_shared_state::<Option<CacheMeta>>(cx).unwrap();
+    let page_meta = page_meta
+        .read()
+        .as_ref()
+        .map(|meta| format!(" | {meta}"))
+        .unwrap_or_default();
+
     cx.render(rsx! {
         h1 {
             "ABSOLUTELY NO WARRANTY"
         },
+        match load_state.get() {
+            LoadState::Loading => rsx! {
+                p {
+                    "Loading exam data..."
+                }
+            },
+            LoadState::Failed(e) => rsx! {
+                p {
+                    "Could not load the default exam data ({e}), choose a source below."
+                }
+            },
+            LoadState::Loaded => rsx! { "" },
+        }
         source_select {},
         br {},
         semesters_display {
@@ -102,7 +206,7 @@ pub fn app(cx: Scope) -> Element {
         br {},
         br {},
         i {
-            version
+            "{version}{page_meta}"
         }
     })
 }
@@ -155,6 +259,7 @@ fn source_select(cx: Scope) -> Element {
         json_source {
             s_type: source_type.get().clone()
         },
+        ics_schedule_source {},
         h2 {
             if source.read().is_some() {
                 "LOADED DATA"
@@ -172,17 +277,13 @@ fn url_source(cx: Scope, s_type: SourceTypeProp) -> Element {
     };
 
     let source = use_shared_state::<Option<CalendarMap>>(cx).unwrap();
+    let page_meta = use_shared_state::<Option<CacheMeta>>(cx).unwrap();
     let path = use_state(cx, || DEFAULT_URL.to_string());
 
     let invalid_url = use_future(cx, (path,), |(path,)| async move {
-        if let Ok(client) = Client::builder().build() {
-            if let Ok(response) = client.head(path.get()).send().await {
-                response.error_for_status().is_err()
-            } else {
-                true
-            }
-        } else {
-            true
+        match ReqwestFetcher::new() {
+            Ok(fetcher) => !fetcher.probe(path.get()).await,
+            Err(_) => true,
         }
     });
 
@@ -209,9 +310,15 @@ fn url_source(cx: Scope, s_type: SourceTypeProp) -> Element {
                 value: BUTTON_TEXT,
                 disabled: *invalid_url.value().unwrap_or(&true),
                 onclick: move |_| {
-                    to_owned!(source, path);
+                    to_owned!(source, page_meta, path);
                     async move {
-                        *source.write() = get_calendars(path.get()).await.ok();
+                        match load_url(path.get()).await {
+                            Ok((cals, meta)) => {
+                                *source.write() = Some(cals);
+                                *page_meta.write() = meta;
+                            }
+                            Err(_) => *source.write() = None,
+                        }
                     }
                 }
             }
@@ -228,6 +335,7 @@ fn json_source(cx: Scope, s_type: SourceTypeProp) -> Element {
     let file_name = use_state(cx, || "".to_string());
 
     let source = use_shared_state::<Option<CalendarMap>>(cx).unwrap();
+    let page_meta = use_shared_state::<Option<CacheMeta>>(cx).unwrap();
 
     cx.render(rsx! {
         div {
@@ -236,12 +344,13 @@ fn json_source(cx: Scope, s_type: SourceTypeProp) -> Element {
                 accept: ".json",
                 id: "json_file",
                 onchange: move |event| {
-                    to_owned!(source, file_name);
+                    to_owned!(source, page_meta, file_name);
                     async move {
                         if let Some(file_engine) = &event.files {
                             let input_file = &file_engine.files()[0];
                             if let Some(contents) = file_engine.read_file_to_string(input_file).await {
                                 *source.write() = serde_json::from_str(&contents).ok();
+                                *page_meta.write() = None;
                                 file_name.set(r"C:\fakepath\".to_string() + input_file);
                             }
                         }
@@ -252,9 +361,52 @@ fn json_source(cx: Scope, s_type: SourceTypeProp) -> Element {
     })
 }
 
+/// Adds every weekly class in an exported class schedule to the personal exam schedule.
+fn ics_schedule_source(cx: Scope) -> Element {
+    let classes = use_shared_state::<MyClasses>(cx).unwrap();
+    let status = use_state(cx, || "".to_string());
+
+    cx.render(rsx! {
+        div {
+            label {
+                r#for: "ics_file",
+                "Import my class schedule (.ics): "
+            },
+            input {
+                r#type: "file",
+                accept: ".ics",
+                id: "ics_file",
+                onchange: move |event| {
+                    to_owned!(classes, status);
+                    async move {
+                        if let Some(file_engine) = &event.files {
+                            let input_file = &file_engine.files()[0];
+                            if let Some(contents) = file_engine.read_file_to_string(input_file).await {
+                                match classes_from_ics(&contents) {
+                                    Ok(imported) => {
+                                        let mut classes = classes.write();
+                                        let count = imported
+                                            .into_iter()
+                                            .filter(|(label, class)| add_class(&mut classes, Some(label.clone()), class.clone()))
+                                            .count();
+                                        status.set(format!(" Imported {count} classes"));
+                                    }
+                                    Err(e) => status.set(format!(" {e}")),
+                                }
+                            }
+                        }
+                    }
+                }
+            },
+            "{status}"
+        }
+    })
+}
+
 #[inline_props]
 fn semesters_display(cx: Scope, source: Option<Option<CalendarMap>>) -> Element {
     let semester = use_shared_state::<Option<Calendar>>(cx).unwrap();
+    let semester_name = use_shared_state::<Option<Semester>>(cx).unwrap();
 
     let semester_options = use_future(cx, (source,), |(source,)| async move {
         if let Some(Some(source)) = source {
@@ -280,9 +432,11 @@ fn semesters_display(cx: Scope, source: Option<Option<CalendarMap>>) -> Element
             select {
                 onchange: move |event| {
                     if let Some(Some(cal_map)) = source {
-                        *semester.write() = cal_map.get(&event.value).cloned();
+                        *semester.write() = cal_map.semester(&event.value).cloned();
+                        *semester_name.write() = event.value.parse().ok();
                     } else {
                         *semester.write() = None;
+                        *semester_name.write() = None;
                     }
                 },
                 name: "sem_select",
@@ -336,14 +490,20 @@ fn get_exam_time(cx: Scope, semester: Option<Option<Calendar>>) -> Element {
                 class_choice: class_choice.read().clone(),
                 semester: semester.clone(),
             },
+            br {},
+            schedule_display {
+                semester: semester.clone(),
+            },
+            br {},
+            day_view {
+                semester: semester.clone(),
+            },
         })
     } else {
         None
     }
 }
 
-const TIME_FORMAT: &str = "%-I:%M %p";
-
 #[inline_props]
 fn day_time_classes(cx: Scope, classes: Vec<Class>) -> Element {
     let class_choice = use_shared_state::<Option<Class>>(cx).unwrap();
@@ -446,8 +606,8 @@ fn day_time_classes(cx: Scope, classes: Vec<Class>) -> Element {
                         flex: true,
                         input {
                             r#type: "checkbox",
-                            name: "select_{day:?}",
-                            value: "{day:?}",
+                            name: "select_{day}",
+                            value: "{day}",
                             onchange: move |event| {
                                 if event.value.to_lowercase() == "true" {
                                     selected_class_days.with_mut(|sels| { sels.push(day.clone()); sels.sort() });
@@ -457,8 +617,8 @@ fn day_time_classes(cx: Scope, classes: Vec<Class>) -> Element {
                             },
                         },
                         label {
-                            r#for: "select_{day:?}",
-                            "{day:?}"
+                            r#for: "select_{day}",
+                            "{day.name()}"
                         },
                     }
                 },
@@ -603,7 +763,7 @@ fn time_range_classes(cx: Scope, classes: Vec<Class>) -> Element {
                     },
                     label {
                         r#for: "select_{range:?}",
-                        "{range.start.format(TIME_FORMAT).to_string()} - {range.end.format(TIME_FORMAT).to_string()}",
+                        "{Class::Range(range.clone())}",
                     },
                 }
             },
@@ -611,21 +771,285 @@ fn time_range_classes(cx: Scope, classes: Vec<Class>) -> Element {
     }})
 }
 
-const DATE_FORMAT: &str = "%A, %B %e";
-
 #[inline_props]
 fn exam_display(cx: Scope, class_choice: Option<Option<Class>>, semester: Calendar) -> Element {
+    let schedule = use_shared_state::<MyClasses>(cx).unwrap();
+    let semester_name = use_shared_state::<Option<Semester>>(cx).unwrap();
+
     if let Some(Some(choice)) = class_choice {
-        if let Some(exam) = semester.get(choice) {
+        if let Some(found) = semester.resolve(choice) {
+            let exams = found.exams;
+            let ics = semester_name
+                .read()
+                .map(|name| classes_to_ics(&name, semester, std::slice::from_ref(choice)));
             return cx.render(rsx! {
                 h4 {
-                    "Exam:",
+                    if exams.len() > 1 {
+                        "Exams (this class is listed more than once):"
+                    } else {
+                        "Exam:"
+                    }
+                },
+                if found.kind != MatchKind::Exact {
+                    rsx! {
+                        i {
+                            "Matched by {found.kind}"
+                        }
+                    }
+                },
+                if let Some(ambiguity) = found.ambiguity() {
+                    rsx! {
+                        p {
+                            b { "Check which listing applies to your class. " },
+                            "{ambiguity}"
+                        }
+                    }
+                },
+                for exam in exams {
+                    p {
+                        "{exam}",
+                        br {},
+                        i {
+                            "{countdown(exam)}"
+                        },
+                    }
+                },
+                input {
+                    r#type: "button",
+                    value: "Add to my schedule",
+                    disabled: schedule.read().iter().any(|(_, class)| class == choice),
+                    onclick: move |_| {
+                        schedule.write().push((None, choice.clone()));
+                    },
+                },
+                if let Some(ics) = ics {
+                    rsx! {
+                        ics_download {
+                            file_name: "exam.ics".to_string(),
+                            contents: ics,
+                        }
+                    }
                 },
-                "{exam.0.format(DATE_FORMAT)}",
-                br {},
-                "{exam.1.start.format(TIME_FORMAT).to_string()} - {exam.1.end.format(TIME_FORMAT).to_string()}",
             });
         }
     }
     None
 }
+
+/// Offers `contents` as a calendar file: a download link in the browser, or
+/// a file written to the working directory on desktop.
+#[inline_props]
+fn ics_download(cx: Scope, file_name: String, contents: String) -> Element {
+    let status = use_state(cx, || "".to_string());
+
+    #[cfg(target_family = "wasm")]
+    {
+        let _ = status;
+        let href = format!(
+            "data:text/calendar;charset=utf-8,{}",
+            percent_encode(contents)
+        );
+        cx.render(rsx! {
+            a {
+                href: "{href}",
+                download: "{file_name}",
+                "Download .ics"
+            }
+        })
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    cx.render(rsx! {
+        input {
+            r#type: "button",
+            value: "Save .ics",
+            onclick: move |_| {
+                match std::fs::write(file_name, contents) {
+                    Ok(()) => status.set(format!(" Saved to ./{file_name}")),
+                    Err(e) => status.set(format!(" Could not save: {e}")),
+                }
+            },
+        },
+        "{status}"
+    })
+}
+
+#[cfg(target_family = "wasm")]
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+#[inline_props]
+fn day_view(cx: Scope, semester: Calendar) -> Element {
+    let slots: Vec<_> = semester
+        .by_exam()
+        .into_iter()
+        .map(|(exam, classes)| {
+            (
+                exam.to_string(),
+                classes
+                    .into_iter()
+                    .map(Class::to_string)
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect();
+
+    cx.render(rsx! {
+        details {
+            summary {
+                "Day view (every class in each exam slot)"
+            },
+            for (slot, classes) in slots.into_iter() {
+                h4 {
+                    "{slot} ({classes.len()} classes)"
+                }
+                ul {
+                    for class in classes.into_iter() {
+                        li {
+                            "{class}"
+                        }
+                    }
+                }
+            },
+        },
+    })
+}
+
+fn countdown(exam: &Exam) -> String {
+    let now = Utc::now();
+    if exam.end() <= now {
+        "Already over".to_string()
+    } else if exam.start() <= now {
+        "In progress".to_string()
+    } else {
+        let left = exam.start().signed_duration_since(now);
+        format!(
+            "Starts in {} days, {} hours, {} minutes",
+            left.num_days(),
+            left.num_hours() % 24,
+            left.num_minutes() % 60
+        )
+    }
+}
+
+#[inline_props]
+fn schedule_display(cx: Scope, semester: Calendar) -> Element {
+    let classes = use_shared_state::<MyClasses>(cx).unwrap();
+    if classes.read().is_empty() {
+        return None;
+    }
+
+    let schedule = Schedule::from_entries(semester, classes.read().iter().cloned());
+    let exams: Vec<_> = schedule
+        .exams
+        .iter()
+        .map(|scheduled| {
+            (
+                (scheduled.label.clone(), scheduled.class.clone()),
+                scheduled.to_string(),
+            )
+        })
+        .collect();
+    let unresolved = schedule.unresolved.clone();
+    let conflicts: Vec<_> = schedule
+        .conflicts()
+        .iter()
+        .map(|conflict| {
+            format!(
+                "{}: {} ({}) and {} ({})",
+                conflict.kind,
+                conflict.first.name(),
+                conflict.first.exam,
+                conflict.second.name(),
+                conflict.second.exam
+            )
+        })
+        .collect();
+    let violations: Vec<_> = RuleReport::new(&Rule::defaults(), &schedule, semester)
+        .violations
+        .iter()
+        .map(|violation| {
+            format!(
+                "{}: {}",
+                violation.rule,
+                violation.exams.iter().map(ScheduledExam::name).join(", ")
+            )
+        })
+        .collect();
+
+    cx.render(rsx! {
+        h3 {
+            "My exam schedule:"
+        },
+        ul {
+            for (entry, exam) in exams.into_iter() {
+                li {
+                    "{exam} "
+                    input {
+                        r#type: "button",
+                        value: "Remove",
+                        onclick: move |_| {
+                            classes.write().retain(|x| x != &entry);
+                        },
+                    }
+                }
+            }
+            for entry in unresolved.into_iter() {
+                li {
+                    "{class_label(&entry.0, &entry.1)}: no exam found "
+                    input {
+                        r#type: "button",
+                        value: "Remove",
+                        onclick: move |_| {
+                            classes.write().retain(|x| x != &entry);
+                        },
+                    }
+                }
+            }
+        },
+        if !conflicts.is_empty() {
+            rsx! {
+                h4 {
+                    "Conflicts:"
+                },
+                ul {
+                    for conflict in conflicts.into_iter() {
+                        li {
+                            "{conflict}"
+                        }
+                    }
+                },
+            }
+        },
+        if !violations.is_empty() {
+            rsx! {
+                h4 {
+                    "Exam overload (you may be able to reschedule):"
+                },
+                ul {
+                    for violation in violations.into_iter() {
+                        li {
+                            "{violation}"
+                        }
+                    }
+                },
+            }
+        },
+        input {
+            r#type: "button",
+            value: "Clear schedule",
+            onclick: move |_| {
+                classes.write().clear();
+            },
+        },
+    })
+}
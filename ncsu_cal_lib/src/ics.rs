@@ -0,0 +1,532 @@
+/*
+* Copyright (C) 2023 Bennett Petzold
+*
+* This file is part of ncsu_exam_calendar.
+*
+* ncsu_exam_calendar is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 2 of the License, or (at your option) any later version.
+*
+* ncsu_exam_calendar is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License along with ncsu_exam_calendar. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashSet;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Datelike, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use itertools::Itertools;
+
+use crate::{
+    calendar::{Calendar, CalendarMap, Class, Exam, Weekday, EXAM_TIMEZONE},
+    schedule::Schedule,
+    semester::Semester,
+};
+
+const PRODID: &str = "-//NCSU Exam Calendar//EN";
+const UID_DOMAIN: &str = "ncsu-exam-calendar";
+
+/// Rules for [`EXAM_TIMEZONE`].
+const VTIMEZONE: &str = "BEGIN:VTIMEZONE\r
+TZID:America/New_York\r
+BEGIN:DAYLIGHT\r
+TZOFFSETFROM:-0500\r
+TZOFFSETTO:-0400\r
+TZNAME:EDT\r
+DTSTART:20070311T020000\r
+RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=2SU\r
+END:DAYLIGHT\r
+BEGIN:STANDARD\r
+TZOFFSETFROM:-0400\r
+TZOFFSETTO:-0500\r
+TZNAME:EST\r
+DTSTART:20071104T020000\r
+RRULE:FREQ=YEARLY;BYMONTH=11;BYDAY=1SU\r
+END:STANDARD\r
+END:VTIMEZONE\r
+";
+
+/// All exams in one semester.
+pub fn calendar_to_ics(semester: &Semester, calendar: &Calendar) -> String {
+    let mut ics = IcsWriter::default();
+    for (class, exam) in calendar.entries().sorted_by_key(|(_, exam)| *exam) {
+        ics.event(
+            semester,
+            class,
+            class,
+            listed_index(calendar, class, exam),
+            exam,
+        );
+    }
+    ics.finish()
+}
+
+/// The exams for `classes` in one semester, resolved like [`Calendar::resolve`].
+///
+/// Events keep the UIDs of the full export, so importing both does not duplicate them.
+pub fn classes_to_ics(semester: &Semester, calendar: &Calendar, classes: &[Class]) -> String {
+    let mut ics = IcsWriter::default();
+    let mut written = HashSet::new();
+    for class in classes {
+        if let Some(found) = calendar.resolve(class) {
+            if !written.insert(found.class) {
+                continue;
+            }
+            for (idx, exam) in found.exams.iter().enumerate() {
+                ics.event(semester, class, found.class, idx, exam);
+            }
+        }
+    }
+    ics.finish()
+}
+
+/// All exams in every semester.
+pub fn calendar_map_to_ics(map: &CalendarMap) -> String {
+    let mut ics = IcsWriter::default();
+    for (semester, calendar) in map.iter().sorted_by_key(|(semester, _)| *semester) {
+        for (class, exam) in calendar.entries().sorted_by_key(|(_, exam)| *exam) {
+            ics.event(
+                semester,
+                class,
+                class,
+                listed_index(calendar, class, exam),
+                exam,
+            );
+        }
+    }
+    ics.finish()
+}
+
+/// Weekly class meetings in a class schedule exported as iCalendar, labeled by event summary.
+///
+/// Events that do not repeat weekly, are all day, or only meet on weekends are left out.
+pub fn classes_from_ics(ics: &str) -> Result<Vec<(String, Class)>> {
+    let unfolded = ics
+        .replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+    if !unfolded
+        .lines()
+        .any(|line| line.trim().eq_ignore_ascii_case("BEGIN:VCALENDAR"))
+    {
+        bail!("Not an iCalendar file (no BEGIN:VCALENDAR)")
+    }
+
+    let mut classes = Vec::new();
+    let mut event: Option<ImportEvent> = None;
+    for line in unfolded.lines() {
+        let Some((head, value)) = line.split_once(':') else {
+            continue;
+        };
+        let mut params = head.split(';');
+        let name = params.next().unwrap_or_default().to_ascii_uppercase();
+        match (name.as_str(), value.trim()) {
+            ("BEGIN", "VEVENT") => event = Some(ImportEvent::default()),
+            ("END", "VEVENT") => {
+                if let Some(class) = event.take().and_then(|event| event.class()) {
+                    if !classes.contains(&class) {
+                        classes.push(class);
+                    }
+                }
+            }
+            (_, value) => {
+                if let Some(event) = &mut event {
+                    match name.as_str() {
+                        "SUMMARY" => event.summary = unescape_text(value),
+                        "DTSTART" => event.start = parse_start(params, value),
+                        "RRULE" => event.rrule = Some(value.to_ascii_uppercase()),
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+    Ok(classes)
+}
+
+/// The exams for every class in a class schedule exported as iCalendar.
+pub fn schedule_from_ics(calendar: &Calendar, ics: &str) -> Result<Schedule> {
+    Ok(Schedule::labeled(calendar, classes_from_ics(ics)?))
+}
+
+#[derive(Debug, Default)]
+struct ImportEvent {
+    summary: String,
+    /// Campus wall clock time, and how many days converting to it moved the date.
+    start: Option<(NaiveDateTime, i64)>,
+    rrule: Option<String>,
+}
+
+impl ImportEvent {
+    fn class(self) -> Option<(String, Class)> {
+        let (start, shift) = self.start?;
+        let rrule = self.rrule?;
+        let rule = |key: &str| {
+            rrule
+                .split(';')
+                .filter_map(|part| part.split_once('='))
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| value)
+        };
+        if rule("FREQ") != Some("WEEKLY") {
+            return None;
+        }
+
+        let days: Vec<_> = match rule("BYDAY") {
+            Some(by_day) => by_day
+                .split(',')
+                .filter_map(|day| {
+                    match day
+                        .trim_start_matches(|c: char| c == '+' || c == '-' || c.is_ascii_digit())
+                    {
+                        "MO" => Some(chrono::Weekday::Mon),
+                        "TU" => Some(chrono::Weekday::Tue),
+                        "WE" => Some(chrono::Weekday::Wed),
+                        "TH" => Some(chrono::Weekday::Thu),
+                        "FR" => Some(chrono::Weekday::Fri),
+                        "SA" => Some(chrono::Weekday::Sat),
+                        "SU" => Some(chrono::Weekday::Sun),
+                        _ => None,
+                    }
+                })
+                .map(|day| shift_weekday(day, shift))
+                .collect(),
+            None => vec![start.weekday()],
+        };
+        let days: Vec<_> = days
+            .into_iter()
+            .filter_map(|day| match day {
+                chrono::Weekday::Mon => Some(Weekday::Monday),
+                chrono::Weekday::Tue => Some(Weekday::Tuesday),
+                chrono::Weekday::Wed => Some(Weekday::Wednesday),
+                chrono::Weekday::Thu => Some(Weekday::Thursday),
+                chrono::Weekday::Fri => Some(Weekday::Friday),
+                _ => None,
+            })
+            .sorted()
+            .dedup()
+            .collect();
+        if days.is_empty() {
+            return None;
+        }
+        Some((self.summary, Class::Time(days, start.time())))
+    }
+}
+
+fn shift_weekday(day: chrono::Weekday, shift: i64) -> chrono::Weekday {
+    (0..shift.rem_euclid(7)).fold(day, |day, _| day.succ())
+}
+
+/// Reads DTSTART as campus wall clock time. Unknown TZIDs are read as wall clock time.
+fn parse_start<'a>(
+    mut params: impl Iterator<Item = &'a str>,
+    value: &str,
+) -> Option<(NaiveDateTime, i64)> {
+    let naive = NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S").ok()?;
+    let tz = params.find_map(|param| {
+        param
+            .split_once('=')
+            .filter(|(name, _)| name.eq_ignore_ascii_case("TZID"))
+            .and_then(|(_, tz)| tz.trim_matches('"').parse::<Tz>().ok())
+    });
+
+    let local = if value.ends_with('Z') {
+        Utc.from_utc_datetime(&naive)
+            .with_timezone(&EXAM_TIMEZONE)
+            .naive_local()
+    } else if let Some(tz) = tz {
+        tz.from_local_datetime(&naive)
+            .earliest()?
+            .with_timezone(&EXAM_TIMEZONE)
+            .naive_local()
+    } else {
+        naive
+    };
+    Some((local, (local.date() - naive.date()).num_days()))
+}
+
+fn unescape_text(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n' | 'N') => out.push('\n'),
+                Some(c) => out.push(c),
+                None => (),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Which of the class's exams this is, in the order the calendar lists them.
+fn listed_index(calendar: &Calendar, class: &Class, exam: &Exam) -> usize {
+    calendar
+        .exams(class)
+        .iter()
+        .position(|listed| listed == exam)
+        .unwrap_or_default()
+}
+
+/// 64 bit FNV-1a, stable across builds and platforms (unlike `DefaultHasher`).
+pub fn fnv1a(value: &str) -> u64 {
+    value.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn escape_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn local_time(instant: DateTime<Tz>) -> String {
+    instant.format("%Y%m%dT%H%M%S").to_string()
+}
+
+struct IcsWriter {
+    out: String,
+    stamp: String,
+}
+
+impl Default for IcsWriter {
+    fn default() -> Self {
+        let mut writer = Self {
+            out: String::new(),
+            stamp: Utc::now().format("%Y%m%dT%H%M%SZ").to_string(),
+        };
+        writer.line("BEGIN:VCALENDAR");
+        writer.line("VERSION:2.0");
+        writer.line(&format!("PRODID:{PRODID}"));
+        writer.line("CALSCALE:GREGORIAN");
+        writer.line("METHOD:PUBLISH");
+        writer.out.push_str(VTIMEZONE);
+        writer
+    }
+}
+
+impl IcsWriter {
+    /// Writes one content line, folded to 75 octets as required by RFC 5545.
+    fn line(&mut self, line: &str) {
+        let mut len = 0;
+        for c in line.chars() {
+            if len + c.len_utf8() > 75 {
+                self.out.push_str("\r\n ");
+                len = 1;
+            }
+            self.out.push(c);
+            len += c.len_utf8();
+        }
+        self.out.push_str("\r\n");
+    }
+
+    /// `class` names the event, and the UID comes from the calendar's `listed` class. `index`
+    /// tells apart the exams of a class listed more than once. The UID leaves out the exam time,
+    /// so a rescheduled exam updates the existing event instead of adding another.
+    fn event(
+        &mut self,
+        semester: &Semester,
+        class: &Class,
+        listed: &Class,
+        index: usize,
+        exam: &Exam,
+    ) {
+        let mut key = format!(
+            "{semester}|{}",
+            serde_json::to_string(listed).unwrap_or_else(|_| listed.to_string())
+        );
+        if index > 0 {
+            key.push_str(&format!("|{index}"));
+        }
+
+        self.line("BEGIN:VEVENT");
+        self.line(&format!("UID:{:016x}@{UID_DOMAIN}", fnv1a(&key)));
+        self.line(&format!("DTSTAMP:{}", self.stamp));
+        self.line(&format!(
+            "DTSTART;TZID={}:{}",
+            EXAM_TIMEZONE.name(),
+            local_time(exam.start())
+        ));
+        self.line(&format!(
+            "DTEND;TZID={}:{}",
+            EXAM_TIMEZONE.name(),
+            local_time(exam.end())
+        ));
+        self.line(&format!(
+            "SUMMARY:{}",
+            escape_text(&format!("{semester} exam: {class}"))
+        ));
+        self.line("END:VEVENT");
+    }
+
+    fn finish(mut self) -> String {
+        self.line("END:VCALENDAR");
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveTime;
+
+    use super::*;
+
+    fn page(first_day: &str) -> String {
+        format!(
+            r#"<h2>Fall 2023 Exam Calendar</h2>
+<table><thead><tr><th>Exam Dates/Times</th><th>8:30 a.m. – 11:00 a.m.</th><th>12:00 p.m. – 2:30 p.m.</th></tr></thead>
+<tbody>
+<tr><td>{first_day}</td><td>10:15 a.m. MW</td><td>Common:<br>PY 211</td></tr>
+<tr><td>Monday, Dec. 11</td><td>11:45 a.m. Th</td><td>Common:<br>PY 211</td></tr>
+</tbody></table>"#
+        )
+    }
+
+    fn fall(page: &str) -> Calendar {
+        let (cals, report) = CalendarMap::from_html_str(page).unwrap();
+        assert!(report.is_empty(), "{report}");
+        cals.semester("Fall 2023").unwrap().clone()
+    }
+
+    fn uid_lines(ics: &str) -> Vec<String> {
+        ics.lines()
+            .filter(|line| line.starts_with("UID:"))
+            .map(str::to_string)
+            .sorted()
+            .collect()
+    }
+
+    fn uids(page: &str) -> Vec<String> {
+        let semester: Semester = "Fall 2023".parse().unwrap();
+        uid_lines(&calendar_to_ics(&semester, &fall(page)))
+    }
+
+    #[test]
+    fn uid_is_stable_when_an_exam_moves() {
+        let before = uids(&page("Friday, Dec. 8"));
+        assert_eq!(before.len(), 4);
+        assert_eq!(before.iter().unique().count(), 4, "{before:?}");
+        assert_eq!(uids(&page("Friday, Dec. 8")), before);
+        assert_eq!(uids(&page("Wednesday, Dec. 13")), before);
+    }
+
+    #[test]
+    fn fnv1a_matches_the_reference_values() {
+        assert_eq!(fnv1a(""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a("a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a("foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn fixture_lists_meeting_times() {
+        let fall = fall(&page("Friday, Dec. 8"));
+        let thursday = Class::Time(
+            vec![Weekday::Thursday],
+            NaiveTime::from_hms_opt(11, 45, 0).unwrap(),
+        );
+        assert_eq!(fall.exams(&thursday).len(), 1);
+    }
+
+    #[test]
+    fn class_export_shares_uids_with_the_full_export() {
+        let fall = fall(&page("Friday, Dec. 8"));
+        let semester: Semester = "Fall 2023".parse().unwrap();
+        let full = uids(&page("Friday, Dec. 8"));
+
+        // Typed differently from the MW listing it matches
+        let typed: Vec<Class> = vec![
+            "MWF 10:15am".parse().unwrap(),
+            "MW 10:15 AM".parse().unwrap(),
+        ];
+        let mine = uid_lines(&classes_to_ics(&semester, &fall, &typed));
+        assert_eq!(mine.len(), 1, "{mine:?}");
+        assert!(full.contains(&mine[0]), "{mine:?} not in {full:?}");
+    }
+
+    fn import(events: &[&str]) -> Vec<(String, Class)> {
+        let mut ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n".to_string();
+        for event in events {
+            ics.push_str(&format!("BEGIN:VEVENT\r\n{event}\r\nEND:VEVENT\r\n"));
+        }
+        ics.push_str("END:VCALENDAR\r\n");
+        classes_from_ics(&ics).unwrap()
+    }
+
+    fn meeting(days: &[Weekday], hour: u32, minute: u32) -> Class {
+        Class::Time(
+            days.to_vec(),
+            NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
+        )
+    }
+
+    #[test]
+    fn import_converts_utc_to_campus_time() {
+        use Weekday::*;
+        // 15:15 UTC is 10:15 EST
+        let classes = import(&[
+            "SUMMARY:CSC 316\r\nDTSTART:20240108T151500Z\r\nRRULE:FREQ=WEEKLY;BYDAY=MO,WE,FR",
+        ]);
+        assert_eq!(
+            classes,
+            [(
+                "CSC 316".to_string(),
+                meeting(&[Monday, Wednesday, Friday], 10, 15)
+            )]
+        );
+    }
+
+    #[test]
+    fn import_shifts_days_across_midnight() {
+        use Weekday::*;
+        // Tuesday 00:15 in Tokyo is Monday 10:15 on campus
+        let classes = import(&[
+            "SUMMARY:MA 341\r\nDTSTART;TZID=Asia/Tokyo:20240109T001500\r\nRRULE:FREQ=WEEKLY;BYDAY=TU,TH",
+        ]);
+        assert_eq!(
+            classes,
+            [("MA 341".to_string(), meeting(&[Monday, Wednesday], 10, 15))]
+        );
+
+        // Friday 22:00 in Los Angeles is Saturday 1:00 on campus, so no weekday is left
+        let classes = import(&[
+            "SUMMARY:Late\r\nDTSTART;TZID=America/Los_Angeles:20240112T220000\r\nRRULE:FREQ=WEEKLY;BYDAY=FR",
+        ]);
+        assert!(classes.is_empty(), "{classes:?}");
+    }
+
+    #[test]
+    fn import_unfolds_and_unescapes() {
+        use Weekday::*;
+        let classes = import(&[
+            "SUMMARY:PY 211\\, Physics \r\n for Engineers\r\nDTSTART;TZID=America/New_York:20240109T\r\n 133000\r\nRRULE:FREQ=WEEKLY;\r\n\tBYDAY=TU,TH",
+        ]);
+        assert_eq!(
+            classes,
+            [(
+                "PY 211, Physics for Engineers".to_string(),
+                meeting(&[Tuesday, Thursday], 13, 30)
+            )]
+        );
+    }
+
+    #[test]
+    fn import_skips_events_that_are_not_weekly_classes() {
+        use Weekday::*;
+        let classes = import(&[
+            "SUMMARY:All day\r\nDTSTART;VALUE=DATE:20240108\r\nRRULE:FREQ=WEEKLY;BYDAY=MO",
+            "SUMMARY:Daily\r\nDTSTART:20240108T151500Z\r\nRRULE:FREQ=DAILY",
+            "SUMMARY:Once\r\nDTSTART:20240108T151500Z",
+            "SUMMARY:Weekend\r\nDTSTART:20240113T151500Z\r\nRRULE:FREQ=WEEKLY;BYDAY=SA,SU",
+            // Without BYDAY, the start date's weekday
+            "SUMMARY:Lab\r\nDTSTART:20240110T190000Z\r\nRRULE:FREQ=WEEKLY;COUNT=15",
+        ]);
+        assert_eq!(classes, [("Lab".to_string(), meeting(&[Wednesday], 14, 0))]);
+        assert!(classes_from_ics("SUMMARY:Not a calendar").is_err());
+    }
+}
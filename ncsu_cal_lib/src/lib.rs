@@ -10,19 +10,29 @@
 * You should have received a copy of the GNU General Public License along with ncsu_exam_calendar. If not, see <https://www.gnu.org/licenses/>.
 */
 
-use reqwest::Client;
+use anyhow::Result;
+use fetch::{PageFetcher, ReqwestFetcher};
 use select::document::Document;
 
+pub mod batch;
+pub mod cache;
 pub mod calendar;
+pub mod diff;
+pub mod fetch;
 pub mod gui;
+pub mod ics;
+pub mod rules;
+pub mod schedule;
+pub mod semester;
 
-async fn get_page_html<S: AsRef<str>>(url: S) -> Result<String, reqwest::Error> {
-    let client = Client::builder().build()?;
-    let response = client.get(url.as_ref()).send().await?;
-    response.text().await
+pub async fn get_page_document<S: AsRef<str>>(url: S) -> Result<Document> {
+    get_page_document_with(&ReqwestFetcher::new()?, url).await
 }
 
-pub async fn get_page_document<S: AsRef<str>>(url: S) -> Result<Document, reqwest::Error> {
-    let html = get_page_html(url).await?;
+pub async fn get_page_document_with<F: PageFetcher, S: AsRef<str>>(
+    fetcher: &F,
+    url: S,
+) -> Result<Document> {
+    let html = fetcher.fetch(url.as_ref()).await?;
     Ok(Document::from(html.as_str()))
 }
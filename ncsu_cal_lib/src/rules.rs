@@ -0,0 +1,293 @@
+/*
+* Copyright (C) 2023 Bennett Petzold
+*
+* This file is part of ncsu_exam_calendar.
+*
+* ncsu_exam_calendar is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 2 of the License, or (at your option) any later version.
+*
+* ncsu_exam_calendar is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License along with ncsu_exam_calendar. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::fmt::Display;
+
+use chrono::Duration;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    calendar::{Calendar, Exam},
+    schedule::{Schedule, ScheduledExam},
+};
+
+/// An exam overload rule, e.g. the ones NCSU allows rescheduling for.
+///
+/// Rules count distinct exams, so a class on the schedule twice only counts once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Rule {
+    /// At least `count` exams on one calendar day.
+    ExamsPerDay { count: usize },
+    /// At least `count` exams starting within a rolling window of `hours`.
+    ExamsInWindow { count: usize, hours: i64 },
+    /// At least `count` exams in back to back exam slots of the calendar.
+    ConsecutiveSlots { count: usize },
+}
+
+impl Rule {
+    /// Three exams in a day, within 24 hours, or in consecutive slots.
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::ExamsPerDay { count: 3 },
+            Self::ExamsInWindow {
+                count: 3,
+                hours: 24,
+            },
+            Self::ConsecutiveSlots { count: 3 },
+        ]
+    }
+
+    pub fn check(&self, schedule: &Schedule, calendar: &Calendar) -> Vec<Violation> {
+        let exams = &schedule.exams;
+        let groups: Vec<Vec<&ScheduledExam>> = match *self {
+            Self::ExamsPerDay { count } => exams
+                .iter()
+                .group_by(|scheduled| scheduled.exam.date)
+                .into_iter()
+                .map(|(_, day)| day.collect::<Vec<_>>())
+                .filter(|day| distinct(day) >= count)
+                .collect(),
+            Self::ExamsInWindow { count, hours } => {
+                let window = Duration::hours(hours);
+                let start = |scheduled: &ScheduledExam| scheduled.exam.start();
+
+                let mut groups = Vec::new();
+                let mut last_end = 0;
+                for (idx, first) in exams.iter().enumerate() {
+                    let end = idx
+                        + exams[idx..]
+                            .iter()
+                            .take_while(|scheduled| start(scheduled) - start(first) < window)
+                            .count();
+                    let group: Vec<_> = exams[idx..end].iter().collect();
+                    // Only report the longest window ending at each exam
+                    if distinct(&group) >= count && end > last_end {
+                        groups.push(group);
+                        last_end = end;
+                    }
+                }
+                groups
+            }
+            Self::ConsecutiveSlots { count } => {
+                let slots: Vec<&Exam> = calendar.by_exam().into_keys().collect();
+                let slot_idx =
+                    |scheduled: &ScheduledExam| slots.binary_search(&&scheduled.exam).ok();
+
+                let mut groups = Vec::new();
+                let mut run: Vec<&ScheduledExam> = Vec::new();
+                let mut prev_idx = None;
+                for (idx, scheduled) in exams
+                    .iter()
+                    .filter_map(|scheduled| Some((slot_idx(scheduled)?, scheduled)))
+                {
+                    match prev_idx {
+                        Some(prev) if idx == prev || idx == prev + 1 => (),
+                        _ => {
+                            if distinct(&run) >= count {
+                                groups.push(run.clone());
+                            }
+                            run.clear();
+                        }
+                    }
+                    run.push(scheduled);
+                    prev_idx = Some(idx);
+                }
+                if distinct(&run) >= count {
+                    groups.push(run);
+                }
+                groups
+            }
+        };
+
+        groups
+            .into_iter()
+            .map(|group| Violation {
+                rule: *self,
+                exams: group.into_iter().cloned().collect(),
+            })
+            .collect()
+    }
+}
+
+fn distinct(group: &[&ScheduledExam]) -> usize {
+    group
+        .iter()
+        .map(|scheduled| &scheduled.exam)
+        .unique()
+        .count()
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ExamsPerDay { count } => write!(f, "{count} or more exams in one day"),
+            Self::ExamsInWindow { count, hours } => {
+                write!(f, "{count} or more exams within {hours} hours")
+            }
+            Self::ConsecutiveSlots { count } => {
+                write!(f, "{count} or more exams in consecutive exam slots")
+            }
+        }
+    }
+}
+
+/// A set of exams that triggers a rule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Violation {
+    pub rule: Rule,
+    pub exams: Vec<ScheduledExam>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleReport {
+    pub violations: Vec<Violation>,
+}
+
+impl RuleReport {
+    pub fn new(rules: &[Rule], schedule: &Schedule, calendar: &Calendar) -> Self {
+        Self {
+            violations: rules
+                .iter()
+                .flat_map(|rule| rule.check(schedule, calendar))
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+impl Display for RuleReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for violation in &self.violations {
+            writeln!(f, "{}:", violation.rule)?;
+            for scheduled in &violation.exams {
+                writeln!(f, "    {scheduled}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveTime};
+
+    use super::*;
+    use crate::calendar::Class;
+
+    /// Every slot of two exam days, and the first of a third.
+    fn calendar() -> Calendar {
+        let exam = |day, (start, end): ((u32, u32), (u32, u32))| {
+            let time = |(hour, minute)| NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+            Exam::new(
+                NaiveDate::from_ymd_opt(2023, 12, day).unwrap(),
+                time(start)..time(end),
+            )
+        };
+        let slots = [
+            ((8, 30), (11, 0)),
+            ((12, 0), (14, 30)),
+            ((15, 30), (18, 0)),
+            ((19, 0), (21, 30)),
+        ];
+        let mut entries = vec![];
+        for (day, names) in [(8, ["A", "B", "C", "D"]), (9, ["E", "F", "G", "H"])] {
+            for (name, slot) in names.into_iter().zip(slots) {
+                entries.push((class(name), exam(day, slot)));
+            }
+        }
+        entries.push((class("I"), exam(11, slots[0])));
+        Calendar::from_iter(entries)
+    }
+
+    fn class(name: &str) -> Class {
+        Class::Name(name.to_string())
+    }
+
+    fn check(rule: Rule, names: &[&str]) -> Vec<Vec<String>> {
+        let calendar = calendar();
+        let schedule = Schedule::new(&calendar, names.iter().map(|name| class(name)));
+        rule.check(&schedule, &calendar)
+            .into_iter()
+            .map(|violation| {
+                violation
+                    .exams
+                    .iter()
+                    .map(|scheduled| scheduled.class.to_string())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exams_per_day() {
+        let rule = Rule::ExamsPerDay { count: 3 };
+        assert_eq!(check(rule, &["A", "B", "D", "E"]), [["A", "B", "D"]]);
+        assert!(check(rule, &["A", "D", "E", "H"]).is_empty());
+    }
+
+    #[test]
+    fn exams_in_window_excludes_exactly_24_hours() {
+        let rule = Rule::ExamsInWindow {
+            count: 3,
+            hours: 24,
+        };
+        // A and E start exactly 24 hours apart
+        assert!(check(rule, &["A", "D", "E"]).is_empty());
+        assert_eq!(check(rule, &["B", "D", "E"]), [["B", "D", "E"]]);
+        assert_eq!(
+            check(rule, &["A", "B", "D", "E"]),
+            [["A", "B", "D"], ["B", "D", "E"]]
+        );
+    }
+
+    #[test]
+    fn consecutive_slots_run_across_days() {
+        let rule = Rule::ConsecutiveSlots { count: 3 };
+        assert_eq!(check(rule, &["C", "D", "E"]), [["C", "D", "E"]]);
+        assert!(check(rule, &["B", "D", "E"]).is_empty());
+        // No exams on the 10th, but the 9th's last slot and the 11th's first are adjacent slots
+        assert_eq!(check(rule, &["G", "H", "I"]), [["G", "H", "I"]]);
+    }
+
+    #[test]
+    fn a_class_listed_twice_counts_once() {
+        let calendar = calendar();
+        let schedule = Schedule::from_entries(
+            &calendar,
+            [
+                (Some("Physics".to_string()), class("A")),
+                (None, class("A")),
+                (None, class("B")),
+            ],
+        );
+        for rule in [
+            Rule::ExamsPerDay { count: 3 },
+            Rule::ExamsInWindow {
+                count: 3,
+                hours: 24,
+            },
+            Rule::ConsecutiveSlots { count: 3 },
+        ] {
+            assert!(rule.check(&schedule, &calendar).is_empty(), "{rule}");
+        }
+        assert_eq!(
+            Rule::ExamsPerDay { count: 2 }.check(&schedule, &calendar)[0]
+                .exams
+                .len(),
+            3
+        );
+    }
+}
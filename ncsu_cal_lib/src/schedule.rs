@@ -0,0 +1,298 @@
+/*
+* Copyright (C) 2023 Bennett Petzold
+*
+* This file is part of ncsu_exam_calendar.
+*
+* ncsu_exam_calendar is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 2 of the License, or (at your option) any later version.
+*
+* ncsu_exam_calendar is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License along with ncsu_exam_calendar. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::fmt::Display;
+
+use chrono::Duration;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::calendar::{Calendar, Class, Exam, MatchKind};
+
+/// One exam on a personal schedule.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ScheduledExam {
+    pub label: Option<String>,
+    /// The class as given to the schedule.
+    pub class: Class,
+    /// The calendar listing it was matched to.
+    pub listed: Class,
+    pub kind: MatchKind,
+    pub exam: Exam,
+}
+
+impl ScheduledExam {
+    /// The label and class, see [`class_label`].
+    pub fn name(&self) -> String {
+        class_label(&self.label, &self.class)
+    }
+}
+
+/// "CSC 316: Friday, December  8, 8:30 AM - 11:00 AM"
+impl Display for ScheduledExam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.name(), self.exam)
+    }
+}
+
+/// Adds `class` to a list of schedule entries unless it is already there, so it is not scheduled
+/// twice. A class already added without a label takes this one. Returns whether it was new.
+pub fn add_class(
+    entries: &mut Vec<(Option<String>, Class)>,
+    label: Option<String>,
+    class: Class,
+) -> bool {
+    match entries.iter_mut().find(|(_, existing)| *existing == class) {
+        Some((existing, _)) => {
+            if existing.is_none() {
+                *existing = label;
+            }
+            false
+        }
+        None => {
+            entries.push((label, class));
+            true
+        }
+    }
+}
+
+/// "CSC 316 (MWF 10:15 AM)", or just the class when it has no label.
+pub fn class_label(label: &Option<String>, class: &Class) -> String {
+    match label {
+        Some(label) => format!("{label} ({class})"),
+        None => class.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ConflictKind {
+    /// Both exams are scheduled at the same time.
+    Overlap,
+    /// The second exam starts (almost) as soon as the first ends.
+    BackToBack,
+}
+
+impl Display for ConflictKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Overlap => "overlapping exams",
+            Self::BackToBack => "back-to-back exams",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    pub first: ScheduledExam,
+    pub second: ScheduledExam,
+}
+
+lazy_static! {
+    /// Largest gap between exams still considered back-to-back by [`Schedule::conflicts`].
+    ///
+    /// NCSU exam slots are an hour apart (8:30-11, 12-2:30, 3:30-6, 7-9:30).
+    pub static ref DEFAULT_MAX_GAP: Duration = Duration::hours(1);
+}
+
+/// The exams for a set of classes in one semester, in chronological order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub exams: Vec<ScheduledExam>,
+    /// Classes that have no exam in the calendar.
+    pub unresolved: Vec<(Option<String>, Class)>,
+}
+
+impl Schedule {
+    pub fn new<I: IntoIterator<Item = Class>>(calendar: &Calendar, classes: I) -> Self {
+        Self::from_entries(calendar, classes.into_iter().map(|class| (None, class)))
+    }
+
+    /// Builds a schedule of classes with a display label each (e.g. the course name).
+    pub fn labeled<S: Into<String>, I: IntoIterator<Item = (S, Class)>>(
+        calendar: &Calendar,
+        classes: I,
+    ) -> Self {
+        Self::from_entries(
+            calendar,
+            classes
+                .into_iter()
+                .map(|(label, class)| (Some(label.into()), class)),
+        )
+    }
+
+    /// Builds a schedule where only some classes have a label.
+    pub fn from_entries<I: IntoIterator<Item = (Option<String>, Class)>>(
+        calendar: &Calendar,
+        classes: I,
+    ) -> Self {
+        let mut schedule = Self::default();
+        for (label, class) in classes {
+            if let Some(found) = calendar.resolve(&class) {
+                schedule
+                    .exams
+                    .extend(found.exams.iter().map(|exam| ScheduledExam {
+                        label: label.clone(),
+                        class: class.clone(),
+                        listed: found.class.clone(),
+                        kind: found.kind,
+                        exam: exam.clone(),
+                    }));
+            } else {
+                schedule.unresolved.push((label, class));
+            }
+        }
+        schedule
+            .exams
+            .sort_by(|a, b| (&a.exam, &a.label).cmp(&(&b.exam, &b.label)));
+        schedule
+    }
+
+    /// Overlapping and back-to-back exams, using [`struct@DEFAULT_MAX_GAP`].
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        self.conflicts_with_gap(*DEFAULT_MAX_GAP)
+    }
+
+    /// Overlapping exams, and exams on the same day separated by at most `max_gap`.
+    pub fn conflicts_with_gap(&self, max_gap: Duration) -> Vec<Conflict> {
+        let mut conflicts = Vec::new();
+        for (idx, first) in self.exams.iter().enumerate() {
+            for second in self.exams[idx + 1..]
+                .iter()
+                .take_while(|second| second.exam.date == first.exam.date)
+            {
+                let (first_exam, second_exam) = (&first.exam, &second.exam);
+                let kind = if first_exam.start() < second_exam.end()
+                    && second_exam.start() < first_exam.end()
+                {
+                    ConflictKind::Overlap
+                } else if second_exam.start() - first_exam.end() <= max_gap {
+                    ConflictKind::BackToBack
+                } else {
+                    continue;
+                };
+                conflicts.push(Conflict {
+                    kind,
+                    first: first.clone(),
+                    second: second.clone(),
+                });
+            }
+        }
+        conflicts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveTime};
+
+    use super::*;
+
+    fn scheduled(name: &str, start: (u32, u32), end: (u32, u32)) -> ScheduledExam {
+        let time = |(hour, minute)| NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+        let class = Class::Name(name.to_string());
+        ScheduledExam {
+            label: None,
+            listed: class.clone(),
+            class,
+            kind: MatchKind::Exact,
+            exam: Exam::new(
+                NaiveDate::from_ymd_opt(2023, 12, 8).unwrap(),
+                time(start)..time(end),
+            ),
+        }
+    }
+
+    #[test]
+    fn adjacent_slots_are_back_to_back() {
+        let schedule = Schedule {
+            exams: vec![
+                scheduled("CSC 316", (8, 30), (11, 0)),
+                scheduled("MA 341", (12, 0), (14, 30)),
+                scheduled("PY 211", (19, 0), (21, 30)),
+            ],
+            unresolved: vec![],
+        };
+        let conflicts: Vec<_> = schedule
+            .conflicts()
+            .into_iter()
+            .map(|conflict| (conflict.kind, conflict.first.class, conflict.second.class))
+            .collect();
+        assert_eq!(
+            conflicts,
+            [(
+                ConflictKind::BackToBack,
+                Class::Name("CSC 316".to_string()),
+                Class::Name("MA 341".to_string())
+            )]
+        );
+        assert!(schedule.conflicts_with_gap(Duration::zero()).is_empty());
+    }
+
+    #[test]
+    fn overlapping_exams_conflict() {
+        let schedule = Schedule {
+            exams: vec![
+                scheduled("CSC 316", (8, 30), (11, 0)),
+                scheduled("PY 205", (10, 15), (12, 45)),
+            ],
+            unresolved: vec![],
+        };
+        let conflicts = schedule.conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].kind, ConflictKind::Overlap);
+    }
+
+    #[test]
+    fn class_listed_twice_overlaps_itself() {
+        let labeled = ScheduledExam {
+            label: Some("Physics".to_string()),
+            ..scheduled("PY 211", (19, 0), (21, 30))
+        };
+        let schedule = Schedule {
+            exams: vec![labeled.clone(), scheduled("PY 211", (19, 0), (21, 30))],
+            unresolved: vec![],
+        };
+        let conflicts = schedule.conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].kind, ConflictKind::Overlap);
+        assert_eq!(conflicts[0].first, labeled);
+        assert_eq!(conflicts[0].first.exam, conflicts[0].second.exam);
+    }
+
+    #[test]
+    fn add_class_skips_classes_already_added() {
+        let mut entries = vec![];
+        assert!(add_class(
+            &mut entries,
+            None,
+            Class::Name("PY 211".to_string())
+        ));
+        assert!(!add_class(
+            &mut entries,
+            Some("Physics".to_string()),
+            Class::Name("PY 211".to_string())
+        ));
+        assert!(!add_class(
+            &mut entries,
+            Some("Other".to_string()),
+            Class::Name("PY 211".to_string())
+        ));
+        assert_eq!(
+            entries,
+            [(
+                Some("Physics".to_string()),
+                Class::Name("PY 211".to_string())
+            )]
+        );
+    }
+}
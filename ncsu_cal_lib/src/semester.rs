@@ -0,0 +1,198 @@
+/*
+* Copyright (C) 2023 Bennett Petzold
+*
+* This file is part of ncsu_exam_calendar.
+*
+* ncsu_exam_calendar is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 2 of the License, or (at your option) any later version.
+*
+* ncsu_exam_calendar is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License along with ncsu_exam_calendar. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{anyhow, bail, Result};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+/// Academic terms, in the order they occur within a calendar year.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Term {
+    Spring,
+    SummerI,
+    Summer,
+    SummerII,
+    Fall,
+}
+
+impl Display for Term {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Spring => "Spring",
+            Self::SummerI => "Summer Session I",
+            Self::Summer => "Summer",
+            Self::SummerII => "Summer Session II",
+            Self::Fall => "Fall",
+        })
+    }
+}
+
+impl FromStr for Term {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = s
+            .to_lowercase()
+            .replace("session", "")
+            .replace(|c: char| c.is_whitespace() || c == '-' || c == '_', "");
+        match normalized.as_str() {
+            "spring" => Ok(Self::Spring),
+            "summeri" | "summer1" => Ok(Self::SummerI),
+            "summer" => Ok(Self::Summer),
+            "summerii" | "summer2" => Ok(Self::SummerII),
+            "fall" => Ok(Self::Fall),
+            _ => bail!(
+                "\"{s}\" is not a known term (Spring, Summer Session I, Summer Session II, Fall)"
+            ),
+        }
+    }
+}
+
+/// A semester, ordered by academic chronology.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Semester {
+    pub year: u16,
+    pub term: Term,
+}
+
+impl Semester {
+    pub fn new(term: Term, year: u16) -> Self {
+        Self { year, term }
+    }
+}
+
+impl Display for Semester {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.term, self.year)
+    }
+}
+
+impl FromStr for Semester {
+    type Err = anyhow::Error;
+    /// Accepts "Fall 2023", "fall-2023", and page headings like
+    /// "Summer Session II 2024 Exam Calendar".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let trimmed = trimmed
+            .strip_suffix("Exam Calendar")
+            .unwrap_or(trimmed)
+            .trim();
+        let words: Vec<_> = trimmed
+            .split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+            .filter(|word| !word.is_empty())
+            .collect();
+
+        let (year_idx, year) = words
+            .iter()
+            .enumerate()
+            .find_map(|(idx, word)| {
+                (word.len() == 4)
+                    .then(|| word.parse::<u16>().ok())
+                    .flatten()
+                    .map(|year| (idx, year))
+            })
+            .ok_or(anyhow!("\"{s}\" does not contain a four digit year"))?;
+        let term = words
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| *idx != year_idx)
+            .map(|(_, word)| word)
+            .join(" ")
+            .parse()?;
+
+        Ok(Self { year, term })
+    }
+}
+
+impl TryFrom<String> for Semester {
+    type Error = anyhow::Error;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Semester> for String {
+    fn from(value: Semester) -> Self {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names_and_page_headings() {
+        for (text, expected) in [
+            ("Fall 2023", Semester::new(Term::Fall, 2023)),
+            ("fall-2023", Semester::new(Term::Fall, 2023)),
+            ("Fall 2023 Exam Calendar", Semester::new(Term::Fall, 2023)),
+            (
+                "Summer Session II 2024 Exam Calendar",
+                Semester::new(Term::SummerII, 2024),
+            ),
+            ("Summer Session I 2024", Semester::new(Term::SummerI, 2024)),
+            ("summer_1 2024", Semester::new(Term::SummerI, 2024)),
+            ("Summer 2024", Semester::new(Term::Summer, 2024)),
+            ("2024 Spring", Semester::new(Term::Spring, 2024)),
+        ] {
+            assert_eq!(text.parse::<Semester>().unwrap(), expected, "{text}");
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_terms_and_missing_years() {
+        for text in [
+            "Winter 2024 Exam Calendar",
+            "Fall",
+            "Fall 23",
+            "",
+            "Fall 2023 2024",
+        ] {
+            assert!(text.parse::<Semester>().is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn display_reads_back() {
+        for term in [
+            Term::Spring,
+            Term::SummerI,
+            Term::Summer,
+            Term::SummerII,
+            Term::Fall,
+        ] {
+            let semester = Semester::new(term, 2024);
+            assert_eq!(semester.to_string().parse::<Semester>().unwrap(), semester);
+        }
+    }
+
+    #[test]
+    fn orders_by_year_then_term() {
+        let ordered = [
+            Semester::new(Term::Fall, 2023),
+            Semester::new(Term::Spring, 2024),
+            Semester::new(Term::SummerI, 2024),
+            Semester::new(Term::Summer, 2024),
+            Semester::new(Term::SummerII, 2024),
+            Semester::new(Term::Fall, 2024),
+            Semester::new(Term::Spring, 2025),
+        ];
+        let mut shuffled = ordered;
+        shuffled.reverse();
+        shuffled.swap(1, 4);
+        shuffled.sort();
+        assert_eq!(shuffled, ordered);
+    }
+}